@@ -0,0 +1,395 @@
+// UCI front end for the engine, so it can be loaded into any chess GUI or tournament manager.
+// The engine modules are shared with the game through #[path], none of them touch bevy.
+#![allow(dead_code)]
+
+#[path = "../chess_engine.rs"]
+mod chess_engine;
+#[path = "../bitboard.rs"]
+mod bitboard;
+#[path = "../square.rs"]
+mod square;
+#[path = "../notation.rs"]
+mod notation;
+#[path = "../evaluation.rs"]
+mod evaluation;
+#[path = "../search.rs"]
+mod search;
+
+use std::io::BufRead;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use chess_engine::{Board, Move, TeamLoyalty, DEFAULTFEN};
+use search::{AnalysisInfo, SearchLimits, StopSignal};
+
+const ENGINE_NAME: &str = "Bitmato Chess";
+const ENGINE_AUTHOR: &str = "Bitmato Studio";
+const MAX_HASH_MB: usize = 1024;
+const MAX_MULTI_PV: usize = 64;
+const BENCH_DEPTH: u32 = 5;
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30); // left over for the GUI talking to us
+
+// the usual perft positions, they cover castling, en passant and promotions between them
+const BENCH_POSITIONS: [&str; 6] = [
+    DEFAULTFEN,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+];
+
+/// Everything `go` can be told, anything missing is None
+#[derive(Debug, Default)]
+struct GoParams {
+    depth: Option<u32>,
+    movetime: Option<Duration>,
+    wtime: Option<Duration>,
+    btime: Option<Duration>,
+    winc: Option<Duration>,
+    binc: Option<Duration>,
+    movestogo: Option<u32>,
+    infinite: bool,
+    perft: Option<u32>,
+}
+
+impl GoParams {
+    fn parse(args: &[&str]) -> Self {
+        let mut params = Self::default();
+        let millis = |value: Option<&&str>| value.and_then(|value| value.parse::<i64>().ok()).map(|ms| Duration::from_millis(ms.max(0) as u64));
+        let number = |value: Option<&&str>| value.and_then(|value| value.parse::<u32>().ok());
+
+        let mut i = 0;
+        while i < args.len() {
+            let value = args.get(i + 1);
+            match args[i] {
+                "depth" => params.depth = number(value),
+                "movetime" => params.movetime = millis(value),
+                "wtime" => params.wtime = millis(value),
+                "btime" => params.btime = millis(value),
+                "winc" => params.winc = millis(value),
+                "binc" => params.binc = millis(value),
+                "movestogo" => params.movestogo = number(value),
+                "perft" => params.perft = number(value),
+                "infinite" => { params.infinite = true; i += 1; continue; },
+                _ => { i += 1; continue; }, // searchmoves, ponder, nodes, mate aren't supported
+            }
+            i += 2;
+        }
+        params
+    }
+
+    // how long to think, None when there's nothing to go by
+    fn think_time(&self, side: TeamLoyalty) -> Option<Duration> {
+        if let Some(movetime) = self.movetime {
+            return Some(movetime.saturating_sub(MOVE_OVERHEAD).max(Duration::from_millis(1)));
+        }
+        let (left, increment) = if side == TeamLoyalty::WHITE { (self.wtime?, self.winc) } else { (self.btime?, self.binc) };
+        let increment = increment.unwrap_or_default();
+        let moves_left = self.movestogo.unwrap_or(30).max(1);
+
+        // a slice of what's left plus most of the increment, never more than half the clock
+        let share = left / moves_left + increment * 3 / 4;
+        let ceiling = (left / 2).saturating_sub(MOVE_OVERHEAD);
+        Some(share.min(ceiling).max(Duration::from_millis(1)))
+    }
+}
+
+struct Engine {
+    board: Board,
+    hash_mb: usize,
+    multi_pv: usize,
+    searching: Option<(StopSignal, JoinHandle<()>)>,
+}
+
+impl Engine {
+    fn new() -> Self {
+        Self {
+            board: Board::create_board(DEFAULTFEN.into()),
+            hash_mb: search::DEFAULT_HASH_MB,
+            multi_pv: 1,
+            searching: None,
+        }
+    }
+
+    fn uci(&self) {
+        println!("id name {}", ENGINE_NAME);
+        println!("id author {}", ENGINE_AUTHOR);
+        println!("option name Hash type spin default {} min 1 max {}", search::DEFAULT_HASH_MB, MAX_HASH_MB);
+        println!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTI_PV);
+        println!("uciok");
+    }
+
+    // "setoption name <id> [value <x>]", the name can have spaces in it
+    fn set_option(&mut self, args: &[&str]) {
+        if args.first() != Some(&"name") {
+            println!("info string setoption needs a name");
+            return;
+        }
+        let value_at = args.iter().position(|arg| *arg == "value").unwrap_or(args.len());
+        let name = args[1.min(value_at)..value_at].join(" ");
+        let value = args.get(value_at + 1..).map(|rest| rest.join(" ")).unwrap_or_default();
+
+        match (name.to_lowercase().as_str(), value.parse::<usize>()) {
+            ("hash", Ok(hash_mb)) => self.hash_mb = hash_mb.clamp(1, MAX_HASH_MB),
+            ("multipv", Ok(lines)) => self.multi_pv = lines.clamp(1, MAX_MULTI_PV),
+            _ => println!("info string unknown option '{}' = '{}'", name, value),
+        }
+    }
+
+    // "position [startpos | fen <fen>] [moves <move>...]"
+    fn position(&mut self, args: &[&str]) {
+        let moves_at = args.iter().position(|arg| *arg == "moves").unwrap_or(args.len());
+        let board = match args.first() {
+            Some(&"startpos") => Ok(Board::create_board(DEFAULTFEN.into())),
+            Some(&"fen") => Board::from_fen(&args[1..moves_at].join(" ")),
+            _ => {
+                println!("info string position needs startpos or fen");
+                return;
+            },
+        };
+        let mut board = match board {
+            Ok(board) => board,
+            Err(err) => {
+                println!("info string bad fen: {}", err);
+                return;
+            },
+        };
+        if let Err(err) = board.validate_position() {
+            println!("info string bad position: {}", err);
+            return;
+        }
+
+        // GUIs play on past repetitions and the fifty move rule, move_entity only stops at checkmate and the like
+        for text in args.iter().skip(moves_at + 1) {
+            let played = Move::from_uci(text)
+                .ok_or_else(|| "not a move".to_owned())
+                .and_then(|mv| chess_engine::move_entity(&mut board, mv, false).map_err(|err| err.to_string()));
+            if let Err(err) = played {
+                // the old position stays, half of this one is no use to anyone
+                println!("info string illegal move {}: {}", text, err);
+                return;
+            }
+        }
+        self.board = board;
+    }
+
+    fn go(&mut self, args: &[&str]) {
+        self.stop();
+        let params = GoParams::parse(args);
+
+        if let Some(depth) = params.perft {
+            perft(&self.board, depth);
+            return;
+        }
+
+        let stop = StopSignal::new();
+        let mut limits = SearchLimits::infinite(stop.clone())
+            .with_hash_mb(self.hash_mb)
+            .with_multi_pv(self.multi_pv);
+        if let Some(depth) = params.depth {
+            limits.depth = depth.clamp(1, search::MAX_DEPTH);
+        }
+        if !params.infinite {
+            limits.time = params.think_time(self.board.current_turn);
+        }
+
+        let board = self.board.clone();
+        let signal = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let mut last: Option<AnalysisInfo> = None;
+            for info in search::analyze(&board, limits) {
+                print_info(&info);
+                last = Some(info);
+            }
+            // an infinite search only answers once it's told to stop, even if it finished early
+            while params.infinite && !signal.is_stopped() {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            print_bestmove(last.as_ref());
+        });
+        self.searching = Some((stop, handle));
+    }
+
+    // waits for the search to hand over its bestmove so nothing gets printed out of order
+    fn stop(&mut self) {
+        if let Some((stop, handle)) = self.searching.take() {
+            stop.stop();
+            let _ = handle.join();
+        }
+    }
+
+    fn bench(&self, args: &[&str]) {
+        let depth = args.first().and_then(|depth| depth.parse::<u32>().ok()).unwrap_or(BENCH_DEPTH);
+        let started = Instant::now();
+        let mut nodes = 0;
+
+        for (i, fen) in BENCH_POSITIONS.iter().enumerate() {
+            let board = Board::from_fen(fen).expect("bench position is broken");
+            let limits = SearchLimits::depth(depth).with_hash_mb(self.hash_mb);
+            let position_started = Instant::now();
+            let searched = search::analyze(&board, limits).last().map(|info| info.nodes).unwrap_or(0);
+            println!("position {}/{}: {} nodes in {} ms", i + 1, BENCH_POSITIONS.len(), searched, position_started.elapsed().as_millis());
+            nodes += searched;
+        }
+
+        let elapsed = started.elapsed();
+        println!("===========================");
+        println!("Total time (ms) : {}", elapsed.as_millis());
+        println!("Nodes searched  : {}", nodes);
+        println!("Nodes/second    : {}", nodes * 1000 / (elapsed.as_millis().max(1) as u64));
+    }
+}
+
+fn score_text(score: i32) -> String {
+    match search::mate_in(score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", score),
+    }
+}
+
+fn print_info(info: &AnalysisInfo) {
+    for (i, line) in info.lines.iter().enumerate() {
+        let pv: Vec<String> = line.pv.iter().map(|mv| mv.to_uci()).collect();
+        println!("info depth {} multipv {} score {} nodes {} nps {} time {} pv {}",
+            info.depth, i + 1, score_text(line.score), info.nodes, info.nodes_per_second(), info.elapsed.as_millis(), pv.join(" "));
+    }
+}
+
+fn print_bestmove(info: Option<&AnalysisInfo>) {
+    let pv = info.and_then(|info| info.best()).map(|line| line.pv.as_slice()).unwrap_or(&[]);
+    match pv {
+        [] => println!("bestmove 0000"), // nothing to play
+        [best] => println!("bestmove {}", best.to_uci()),
+        [best, ponder, ..] => println!("bestmove {} ponder {}", best.to_uci(), ponder.to_uci()),
+    }
+}
+
+fn perft(board: &Board, depth: u32) {
+    let started = Instant::now();
+    let divide = board.perft_divide(depth);
+    for (mv, nodes) in &divide {
+        println!("{}: {}", mv.to_uci(), nodes);
+    }
+    let total: u64 = divide.iter().map(|(_, nodes)| nodes).sum();
+    println!();
+    println!("Nodes searched: {} ({} ms)", total, started.elapsed().as_millis());
+}
+
+fn main() {
+    let mut engine = Engine::new();
+    let stdin = std::io::stdin();
+
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => continue,
+        };
+
+        match command {
+            "uci" => engine.uci(),
+            "isready" => println!("readyok"),
+            "setoption" => engine.set_option(args),
+            "ucinewgame" => {
+                engine.stop();
+                engine.board = Board::create_board(DEFAULTFEN.into());
+            },
+            "position" => {
+                engine.stop();
+                engine.position(args);
+            },
+            "go" => engine.go(args),
+            "stop" => engine.stop(),
+            "bench" => engine.bench(args),
+            "d" => println!("{}\nFen: {}", engine.board.to_string(), engine.board.to_fen()),
+            "quit" => break,
+            _ => println!("info string unknown command '{}'", command),
+        }
+    }
+    engine.stop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(ms: u64) -> Option<Duration> {
+        Some(Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn parses_go() {
+        let params = GoParams::parse(&["wtime", "60000", "btime", "-5", "winc", "1000", "movestogo", "20", "depth", "x", "ponder"]);
+        assert_eq!(params.wtime, millis(60000));
+        assert_eq!(params.btime, millis(0));
+        assert_eq!(params.winc, millis(1000));
+        assert_eq!(params.binc, None);
+        assert_eq!(params.movestogo, Some(20));
+        assert_eq!(params.depth, None);
+        assert!(!params.infinite);
+
+        let params = GoParams::parse(&["infinite", "depth", "7", "movetime"]);
+        assert!(params.infinite);
+        assert_eq!(params.depth, Some(7));
+        assert_eq!(params.movetime, None);
+
+        assert_eq!(GoParams::parse(&["perft", "4"]).perft, Some(4));
+    }
+
+    #[test]
+    fn shares_out_the_clock() {
+        let params = GoParams::parse(&["movetime", "500"]);
+        assert_eq!(params.think_time(TeamLoyalty::BLACK), millis(470));
+        assert_eq!(GoParams::parse(&["movetime", "10"]).think_time(TeamLoyalty::WHITE), millis(1));
+
+        // a thirtieth of what's left plus three quarters of the increment
+        let params = GoParams::parse(&["wtime", "60000", "winc", "1000", "btime", "30000"]);
+        assert_eq!(params.think_time(TeamLoyalty::WHITE), millis(2750));
+        assert_eq!(params.think_time(TeamLoyalty::BLACK), millis(1000));
+
+        // never more than half the clock, even with a big increment
+        let params = GoParams::parse(&["btime", "1000", "binc", "5000", "movestogo", "1"]);
+        assert_eq!(params.think_time(TeamLoyalty::BLACK), millis(470));
+
+        assert_eq!(GoParams::parse(&["depth", "5"]).think_time(TeamLoyalty::WHITE), None);
+        assert_eq!(GoParams::parse(&["wtime", "1000"]).think_time(TeamLoyalty::BLACK), None);
+    }
+
+    #[test]
+    fn sets_options() {
+        let mut engine = Engine::new();
+        engine.set_option(&["name", "Hash", "value", "64"]);
+        engine.set_option(&["name", "MultiPV", "value", "3"]);
+        assert_eq!((engine.hash_mb, engine.multi_pv), (64, 3));
+
+        engine.set_option(&["name", "hash", "value", "100000"]);
+        engine.set_option(&["name", "MultiPV", "value", "0"]);
+        assert_eq!((engine.hash_mb, engine.multi_pv), (MAX_HASH_MB, 1));
+
+        // none of these should change anything (or panic)
+        for args in [&["value", "3"][..], &["name"], &["name", "value"], &["name", "Hash", "value"], &["name", "Hash", "value", "big"], &[]] {
+            engine.set_option(args);
+        }
+        assert_eq!((engine.hash_mb, engine.multi_pv), (MAX_HASH_MB, 1));
+    }
+
+    #[test]
+    fn keeps_the_old_position_on_a_bad_move() {
+        let mut engine = Engine::new();
+        engine.position(&["startpos", "moves", "e2e4", "e7e5"]);
+        let before = engine.board.to_fen();
+        engine.position(&["startpos", "moves", "d2d4", "e2e5"]);
+        assert_eq!(engine.board.to_fen(), before);
+        engine.position(&["fen", "8/8/8/8/8/8/8/8", "w", "-", "-", "0", "1"]);
+        assert_eq!(engine.board.to_fen(), before);
+
+        // repeating the position doesn't stop the replay
+        engine.position(&["startpos", "moves", "g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8", "e2e4"]);
+        assert_eq!(engine.board.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 5");
+    }
+}
@@ -0,0 +1,281 @@
+use crate::chess_engine::{Cell, EntityType, GameEntity, Position, TeamLoyalty, Vec2};
+
+/// A set of squares, bit `y * 8 + x` is the square at `Vec2 { x, y }` (so bit 0 is a8 and bit 63 is h1)
+pub type Bitboard = u64;
+
+pub fn square_index(pos: Position) -> usize {
+    (pos.y * 8 + pos.x) as usize
+}
+
+pub fn index_position(square: usize) -> Position {
+    Vec2 { x: (square % 8) as i32, y: (square / 8) as i32 }
+}
+
+pub fn square_bit(pos: Position) -> Bitboard {
+    1 << square_index(pos)
+}
+
+/// Walks the squares of a set from a8 towards h1
+pub struct Squares(pub Bitboard);
+
+impl Iterator for Squares {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+}
+
+/// Piece types in the order the per-piece sets are stored
+pub const PIECE_TYPES: [EntityType; 6] = [EntityType::PAWN, EntityType::KNIGHT, EntityType::BISHOP, EntityType::ROOK, EntityType::QUEEN, EntityType::KING];
+
+pub fn team_index(team: TeamLoyalty) -> Option<usize> {
+    match team {
+        TeamLoyalty::WHITE => Some(0),
+        TeamLoyalty::BLACK => Some(1),
+        TeamLoyalty::NONE => None,
+    }
+}
+
+pub fn piece_index(entity_type: EntityType) -> Option<usize> {
+    PIECE_TYPES.iter().position(|piece| *piece == entity_type)
+}
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_OFFSETS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+const WHITE_PAWN_OFFSETS: [(i32, i32); 2] = [(-1, -1), (1, -1)]; // white pawns walk up the rows
+const BLACK_PAWN_OFFSETS: [(i32, i32); 2] = [(-1, 1), (1, 1)];
+
+// the first four are the rook directions, the last four the bishop ones
+const DIRECTIONS: [(i32, i32); 8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+const fn on_board(x: i32, y: i32) -> bool {
+    x >= 0 && x < 8 && y >= 0 && y < 8
+}
+
+// squares a single jump away for every starting square
+const fn leaper_table(offsets: &[(i32, i32)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let (x, y) = ((square % 8) as i32, (square / 8) as i32);
+        let mut i = 0;
+        while i < offsets.len() {
+            let (to_x, to_y) = (x + offsets[i].0, y + offsets[i].1);
+            if on_board(to_x, to_y) {
+                table[square] |= 1 << (to_y * 8 + to_x);
+            }
+            i += 1;
+        }
+        square += 1;
+    }
+    table
+}
+
+// everything from a square to the edge of the board in each direction, the square itself left out
+const fn ray_table() -> [[Bitboard; 64]; 8] {
+    let mut table = [[0; 64]; 8];
+    let mut dir = 0;
+    while dir < 8 {
+        let mut square = 0;
+        while square < 64 {
+            let (mut x, mut y) = ((square % 8) as i32 + DIRECTIONS[dir].0, (square / 8) as i32 + DIRECTIONS[dir].1);
+            while on_board(x, y) {
+                table[dir][square] |= 1 << (y * 8 + x);
+                x += DIRECTIONS[dir].0;
+                y += DIRECTIONS[dir].1;
+            }
+            square += 1;
+        }
+        dir += 1;
+    }
+    table
+}
+
+static KNIGHT_ATTACKS: [Bitboard; 64] = leaper_table(&KNIGHT_OFFSETS);
+static KING_ATTACKS: [Bitboard; 64] = leaper_table(&KING_OFFSETS);
+static PAWN_ATTACKS: [[Bitboard; 64]; 2] = [leaper_table(&WHITE_PAWN_OFFSETS), leaper_table(&BLACK_PAWN_OFFSETS)];
+static RAYS: [[Bitboard; 64]; 8] = ray_table();
+
+pub fn knight_attacks(square: usize) -> Bitboard {
+    KNIGHT_ATTACKS[square]
+}
+
+pub fn king_attacks(square: usize) -> Bitboard {
+    KING_ATTACKS[square]
+}
+
+/// The two squares a pawn of `team` on `square` captures on
+pub fn pawn_attacks(team: TeamLoyalty, square: usize) -> Bitboard {
+    match team_index(team) {
+        Some(team) => PAWN_ATTACKS[team][square],
+        None => 0,
+    }
+}
+
+// the ray is cut off just past the first piece in the way (that piece can still be captured)
+fn ray_attacks(dir: usize, square: usize, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[dir][square];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+    let (dx, dy) = DIRECTIONS[dir];
+    let nearest = if dy * 8 + dx > 0 { blockers.trailing_zeros() } else { 63 - blockers.leading_zeros() };
+    ray ^ RAYS[dir][nearest as usize]
+}
+
+pub fn rook_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    (0..4).fold(0, |attacks, dir| attacks | ray_attacks(dir, square, occupied))
+}
+
+pub fn bishop_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    (4..8).fold(0, |attacks, dir| attacks | ray_attacks(dir, square, occupied))
+}
+
+pub fn queen_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    rook_attacks(square, occupied) | bishop_attacks(square, occupied)
+}
+
+/// Squares strictly between two squares on one line, empty if they don't share a line
+pub fn between(a: usize, b: usize) -> Bitboard {
+    let (bit_a, bit_b) = (1 << a, 1 << b);
+    if rook_attacks(a, 0) & bit_b != 0 {
+        rook_attacks(a, bit_b) & rook_attacks(b, bit_a)
+    } else if bishop_attacks(a, 0) & bit_b != 0 {
+        bishop_attacks(a, bit_b) & bishop_attacks(b, bit_a)
+    } else {
+        0
+    }
+}
+
+/// One square set per team and piece type, kept in step with the board's cells
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bitboards {
+    pub pieces: [[Bitboard; 6]; 2], // [team][piece], see PIECE_TYPES
+    pub teams: [Bitboard; 2],
+    pub occupied: Bitboard,
+}
+
+impl Bitboards {
+    pub fn from_cells(cells: &[Vec<Cell>]) -> Self {
+        let mut bitboards = Self::default();
+        for (y, row) in cells.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if let Some(ent) = &cell.occupier {
+                    bitboards.toggle(ent, y * 8 + x);
+                }
+            }
+        }
+        bitboards
+    }
+
+    /// Adds the piece to `square` if it isn't there, takes it off if it is
+    pub fn toggle(&mut self, ent: &GameEntity, square: usize) {
+        if let (Some(team), Some(piece)) = (team_index(ent.team_id), piece_index(ent.entity_type)) {
+            let bit = 1 << square;
+            self.pieces[team][piece] ^= bit;
+            self.teams[team] ^= bit;
+            self.occupied ^= bit;
+        }
+    }
+
+    pub fn piece(&self, team: TeamLoyalty, entity_type: EntityType) -> Bitboard {
+        match (team_index(team), piece_index(entity_type)) {
+            (Some(team), Some(piece)) => self.pieces[team][piece],
+            _ => 0,
+        }
+    }
+
+    pub fn team(&self, team: TeamLoyalty) -> Bitboard {
+        match team_index(team) {
+            Some(team) => self.teams[team],
+            None => 0,
+        }
+    }
+
+    pub fn piece_at(&self, square: usize) -> Option<(TeamLoyalty, EntityType)> {
+        let bit = 1 << square;
+        for team in [TeamLoyalty::WHITE, TeamLoyalty::BLACK] {
+            if self.team(team) & bit == 0 { continue; }
+            for entity_type in PIECE_TYPES {
+                if self.piece(team, entity_type) & bit != 0 {
+                    return Some((team, entity_type));
+                }
+            }
+        }
+        None
+    }
+
+    pub fn king_square(&self, team: TeamLoyalty) -> Option<usize> {
+        let kings = self.piece(team, EntityType::KING);
+        if kings == 0 { None } else { Some(kings.trailing_zeros() as usize) }
+    }
+
+    /// Every piece of `by` that could capture on `square`
+    pub fn attackers_to(&self, square: usize, by: TeamLoyalty) -> Bitboard {
+        let piece = |entity_type| self.piece(by, entity_type);
+        let queens = piece(EntityType::QUEEN);
+
+        // a pawn of `by` hits this square from wherever an enemy pawn here would hit
+        (pawn_attacks(by.opposite(), square) & piece(EntityType::PAWN))
+            | (knight_attacks(square) & piece(EntityType::KNIGHT))
+            | (king_attacks(square) & piece(EntityType::KING))
+            | (rook_attacks(square, self.occupied) & (piece(EntityType::ROOK) | queens))
+            | (bishop_attacks(square, self.occupied) & (piece(EntityType::BISHOP) | queens))
+    }
+
+    pub fn is_attacked(&self, square: usize, by: TeamLoyalty) -> bool {
+        self.attackers_to(square, by) != 0
+    }
+
+    /// Squares a single piece of `team` on `square` hits with the board as it is
+    pub fn piece_attacks(&self, team: TeamLoyalty, entity_type: EntityType, square: usize) -> Bitboard {
+        match entity_type {
+            EntityType::PAWN => pawn_attacks(team, square),
+            EntityType::KNIGHT => knight_attacks(square),
+            EntityType::BISHOP => bishop_attacks(square, self.occupied),
+            EntityType::ROOK => rook_attacks(square, self.occupied),
+            EntityType::QUEEN => queen_attacks(square, self.occupied),
+            EntityType::KING => king_attacks(square),
+            EntityType::NOTSET => 0,
+        }
+    }
+
+    /// Every square `team` hits, own pieces included (those are defended)
+    pub fn attacks(&self, team: TeamLoyalty) -> Bitboard {
+        let mut attacks = 0;
+        for entity_type in PIECE_TYPES {
+            for square in Squares(self.piece(team, entity_type)) {
+                attacks |= self.piece_attacks(team, entity_type, square);
+            }
+        }
+        attacks
+    }
+
+    /// Pieces of `team` that can't leave the line between their king and an enemy slider
+    pub fn pinned(&self, team: TeamLoyalty) -> Bitboard {
+        let king = match self.king_square(team) {
+            Some(king) => king,
+            None => return 0,
+        };
+        let enemy = team.opposite();
+        let queens = self.piece(enemy, EntityType::QUEEN);
+        let snipers = (rook_attacks(king, 0) & (self.piece(enemy, EntityType::ROOK) | queens))
+            | (bishop_attacks(king, 0) & (self.piece(enemy, EntityType::BISHOP) | queens));
+
+        let mut pinned = 0;
+        for sniper in Squares(snipers) {
+            let blockers = between(king, sniper) & self.occupied;
+            if blockers.count_ones() == 1 && blockers & self.team(team) != 0 {
+                pinned |= blockers;
+            }
+        }
+        pinned
+    }
+}
@@ -1,15 +1,9 @@
-/*
-    TODO: Check for check(mate)
-    TODO: Check for game over condition
- */
+use crate::bitboard::*;
+use crate::square::Square;
 
-use std::{cmp::Ordering, ops::Range};
+pub const DEFAULTFEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
-use crate::components::create_range_vector;
-
-pub const DEFAULTFEN: &str = "rnbkqbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w";
-
-#[derive(Debug, Clone, PartialEq, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, Copy)]
 pub enum TeamLoyalty {
     NONE,
     WHITE,
@@ -22,7 +16,7 @@ impl Default for TeamLoyalty {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, Copy)]
 pub enum EntityType {
     NOTSET,
     PAWN,
@@ -39,7 +33,7 @@ impl Default for EntityType{
     }
 }
 
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Vec2 {
     pub x: i32,
     pub y: i32,
@@ -105,15 +99,304 @@ impl Cell {
     }
 }
 
+/// Which castles are still available, lost once the king or that rook moves (or the rook is taken)
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool,
+}
+
+impl CastlingRights {
+    pub fn king_side(&self, team: TeamLoyalty) -> bool {
+        match team {
+            TeamLoyalty::WHITE => self.white_king_side,
+            TeamLoyalty::BLACK => self.black_king_side,
+            TeamLoyalty::NONE => false,
+        }
+    }
+
+    pub fn queen_side(&self, team: TeamLoyalty) -> bool {
+        match team {
+            TeamLoyalty::WHITE => self.white_queen_side,
+            TeamLoyalty::BLACK => self.black_queen_side,
+            TeamLoyalty::NONE => false,
+        }
+    }
+
+    fn clear_team(&mut self, team: TeamLoyalty) {
+        match team {
+            TeamLoyalty::WHITE => { self.white_king_side = false; self.white_queen_side = false; },
+            TeamLoyalty::BLACK => { self.black_king_side = false; self.black_queen_side = false; },
+            TeamLoyalty::NONE => {},
+        }
+    }
+
+    // a rook leaving (or being captured on) its starting corner
+    fn clear_corner(&mut self, pos: Position) {
+        match (pos.x, pos.y) {
+            (7, 7) => self.white_king_side = false,
+            (0, 7) => self.white_queen_side = false,
+            (7, 0) => self.black_king_side = false,
+            (0, 0) => self.black_queen_side = false,
+            _ => {},
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct Board {
     pub cells: Vec<Vec<Cell>>,
     pub current_turn: TeamLoyalty,
+    pub castling: CastlingRights,
+    pub en_passant: Option<Position>,
+    pub halfmove_clock: u32,  // plies since the last capture or pawn move
+    pub fullmove_number: u32, // starts at 1, goes up after black moves
     pub is_check: bool,
     pub is_checkmate: bool,
+    pub is_stalemate: bool,
     pub who_in_check: TeamLoyalty,
+    pub bitboards: Bitboards, // the same pieces as `cells`, used for move generation
+    pub hash: u64, // zobrist key, kept up to date by move_entity
+    pub position_history: Vec<u64>, // hashes of every position so far, current one last
+    pub move_history: Vec<UndoInfo>, // every move made on this board, last one on top
+}
+
+/// Where the game stands after the last move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOutcome {
+    Ongoing,
+    Checkmate(TeamLoyalty), // the winner
+    Stalemate,
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    InsufficientMaterial,
+}
+
+impl GameOutcome {
+    pub fn is_over(&self) -> bool {
+        *self != GameOutcome::Ongoing
+    }
+
+    pub fn is_draw(&self) -> bool {
+        !matches!(self, GameOutcome::Ongoing | GameOutcome::Checkmate(_))
+    }
+
+    /// Nothing can be played after these, unlike repetition and the fifty move rule
+    /// which only end the game once somebody calls it
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, GameOutcome::Checkmate(_) | GameOutcome::Stalemate | GameOutcome::InsufficientMaterial)
+    }
+
+    /// Repetition and the fifty move rule, either player may call the game a draw
+    pub fn is_claimable(&self) -> bool {
+        matches!(self, GameOutcome::ThreefoldRepetition | GameOutcome::FiftyMoveRule)
+    }
+
+    /// `game_end` for the outcomes that finish the game by themselves, claimable draws are left out
+    pub fn forced_end(&self) -> Option<GameEnd> {
+        if self.is_terminal() { self.game_end() } else { None }
+    }
+
+    /// The result and reason for a finished game, None while it is still going
+    pub fn game_end(&self) -> Option<GameEnd> {
+        let termination = match self {
+            GameOutcome::Ongoing => return None,
+            GameOutcome::Checkmate(winner) => return Some(GameEnd::new(GameResult::win_for(*winner), Termination::Checkmate)),
+            GameOutcome::Stalemate => Termination::Stalemate,
+            GameOutcome::FiftyMoveRule => Termination::FiftyMoveRule,
+            GameOutcome::ThreefoldRepetition => Termination::Repetition,
+            GameOutcome::InsufficientMaterial => Termination::InsufficientMaterial,
+        };
+        Some(GameEnd::new(GameResult::Draw, termination))
+    }
+}
+
+/// Final score of a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl GameResult {
+    pub fn win_for(team: TeamLoyalty) -> Self {
+        match team {
+            TeamLoyalty::WHITE => GameResult::WhiteWins,
+            TeamLoyalty::BLACK => GameResult::BlackWins,
+            TeamLoyalty::NONE => GameResult::Draw,
+        }
+    }
+
+    pub fn winner(&self) -> Option<TeamLoyalty> {
+        match self {
+            GameResult::WhiteWins => Some(TeamLoyalty::WHITE),
+            GameResult::BlackWins => Some(TeamLoyalty::BLACK),
+            GameResult::Draw => None,
+        }
+    }
+
+    /// "1-0", "0-1" or "1/2-1/2"
+    pub fn to_pgn(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        }
+    }
+
+    pub fn from_pgn(text: &str) -> Option<Self> {
+        match text {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            _ => None,
+        }
+    }
+}
+
+/// Why a game stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    Checkmate,
+    Resignation,
+    Timeout,
+    Stalemate,
+    Repetition,
+    FiftyMoveRule,
+    InsufficientMaterial,
+    Agreement,
+    Abandonment,
+}
+
+impl Termination {
+    const NAMES: [(Termination, &'static str); 9] = [
+        (Termination::Checkmate, "checkmate"),
+        (Termination::Resignation, "resignation"),
+        (Termination::Timeout, "timeout"),
+        (Termination::Stalemate, "stalemate"),
+        (Termination::Repetition, "repetition"),
+        (Termination::FiftyMoveRule, "fifty-move"),
+        (Termination::InsufficientMaterial, "insufficient-material"),
+        (Termination::Agreement, "agreement"),
+        (Termination::Abandonment, "abandonment"),
+    ];
+
+    /// Short name used on the wire, "checkmate", "timeout", ...
+    pub fn name(&self) -> &'static str {
+        Self::NAMES.iter().find(|(term, _)| term == self).map(|(_, name)| *name).unwrap()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES.iter().find(|(_, n)| *n == name).map(|(term, _)| *term)
+    }
+
+    /// Value for the PGN "Termination" tag, which only has a few standard reasons
+    pub fn pgn_termination(&self) -> &'static str {
+        match self {
+            Termination::Timeout => "time forfeit",
+            Termination::Abandonment => "abandoned",
+            _ => "normal",
+        }
+    }
+}
+
+/// How a game finished, the result and the reason together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameEnd {
+    pub result: GameResult,
+    pub termination: Termination,
+}
+
+impl GameEnd {
+    pub fn new(result: GameResult, termination: Termination) -> Self {
+        Self { result, termination }
+    }
+
+    /// `team` gave up, ran out of time or walked away, the other side wins
+    pub fn loss_for(team: TeamLoyalty, termination: Termination) -> Self {
+        Self::new(GameResult::win_for(team.opposite()), termination)
+    }
+
+    /// "1-0 checkmate", what goes out with the EM message
+    pub fn to_message(&self) -> String {
+        format!("{} {}", self.result.to_pgn(), self.termination.name())
+    }
+
+    pub fn from_message(text: &str) -> Option<Self> {
+        let (result, termination) = text.trim().split_once(' ')?;
+        Some(Self::new(GameResult::from_pgn(result)?, Termination::from_name(termination.trim())?))
+    }
+}
+
+/// Why a FEN string was rejected by `Board::from_fen`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    Empty,
+    TooManyFields(usize),
+    WrongRankCount(usize),
+    WrongRankLength { rank: usize, length: usize },
+    InvalidPiece(char),
+    MissingSideToMove,
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::Empty => write!(f, "FEN is empty"),
+            FenError::TooManyFields(count) => write!(f, "expected at most 6 fields, found {}", count),
+            FenError::WrongRankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            FenError::WrongRankLength { rank, length } => write!(f, "rank {} has {} squares instead of 8", rank, length),
+            FenError::InvalidPiece(c) => write!(f, "'{}' is not a piece or empty square count", c),
+            FenError::MissingSideToMove => write!(f, "missing side to move"),
+            FenError::InvalidSideToMove(side) => write!(f, "side to move must be 'w' or 'b', found '{}'", side),
+            FenError::InvalidCastling(rights) => write!(f, "invalid castling availability '{}'", rights),
+            FenError::InvalidEnPassant(square) => write!(f, "invalid en passant square '{}'", square),
+            FenError::InvalidHalfmoveClock(clock) => write!(f, "invalid halfmove clock '{}'", clock),
+            FenError::InvalidFullmoveNumber(number) => write!(f, "invalid fullmove number '{}'", number),
+        }
+    }
 }
 
+impl std::error::Error for FenError {}
+
+/// Why `Board::validate_position` considers a position impossible to reach
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PositionError {
+    MissingKing(TeamLoyalty),
+    TooManyKings(TeamLoyalty),
+    TooManyPawns(TeamLoyalty),
+    TooManyPieces(TeamLoyalty),
+    PawnOnBackRank(Position),
+    OpponentInCheck,
+    InvalidCastlingRights,
+    InvalidEnPassant(Position),
+}
+
+impl std::fmt::Display for PositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PositionError::MissingKing(team) => write!(f, "{:?} has no king", team),
+            PositionError::TooManyKings(team) => write!(f, "{:?} has more than one king", team),
+            PositionError::TooManyPawns(team) => write!(f, "{:?} has more than 8 pawns", team),
+            PositionError::TooManyPieces(team) => write!(f, "{:?} has more than 16 pieces", team),
+            PositionError::PawnOnBackRank(pos) => write!(f, "pawn on {} can't stand on the first or last rank", position_to_algebraic(*pos)),
+            PositionError::OpponentInCheck => write!(f, "the side not to move is in check"),
+            PositionError::InvalidCastlingRights => write!(f, "castling rights don't match the king and rook placement"),
+            PositionError::InvalidEnPassant(pos) => write!(f, "{} can't be the en passant square", position_to_algebraic(*pos)),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
 // just lowercase rnqkbp make upper if needed
 pub fn get_entity_fen(ent_type: &EntityType) -> char {
     match ent_type {
@@ -148,216 +431,755 @@ pub fn make_entity(fen_equiv:char, team: TeamLoyalty) -> GameEntity {
 }
 
 pub fn create_cell(fen_equiv:char) -> Cell {
+    // anything that isn't a piece letter (digits included) is an empty square
+    if get_entity_type(fen_equiv) == EntityType::NOTSET {
+        return Cell::default();
+    }
 
-    // start looking for numbers
-    // 9 in ascii is 57 if so we can
-    // start checking for numbers (only using 1 if cause lazy)
-    // 
-    let game_entity:Option<GameEntity> = if fen_equiv < 57 as char {
-        None
-    } else {
-        let loyalty = if fen_equiv < 97 as char {
-            TeamLoyalty::WHITE
-        } else { 
-            TeamLoyalty::BLACK 
-        };
-        Some(make_entity(fen_equiv, loyalty))
+    let loyalty = if fen_equiv.is_ascii_uppercase() {
+        TeamLoyalty::WHITE
+    } else { 
+        TeamLoyalty::BLACK 
     };
 
     Cell {
         is_occupied: true,
-        occupier: game_entity,
+        occupier: Some(make_entity(fen_equiv, loyalty)),
         cell_fen_repr: fen_equiv,
     }
 }
 
-fn d(from: Vec2, to: Vec2) -> (usize, usize) {
-    let dx = match from.x.cmp(&to.x) {
-        Ordering::Less => to.x - from.x,
-        Ordering::Greater => from.x - to.x,
-        Ordering::Equal => 0,
-    };
+/// What else happens on the board besides the piece changing squares
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveFlags {
+    pub capture: bool,
+    pub castle: bool,
+    pub en_passant: bool,
+    pub double_push: bool,
+}
 
-    let dy = match from.y.cmp(&to.y) {
-        Ordering::Less => to.y - from.y,
-        Ordering::Greater => from.y - to.y,
-        Ordering::Equal => 0,
-    };
+/// A single move of the piece standing on `from` to `to`
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    pub from: Position,
+    pub to: Position,
+    pub promotion: Option<EntityType>, // what a pawn reaching the last rank turns into
+    pub flags: MoveFlags,
+}
+
+pub const PROMOTION_CHOICES: [EntityType; 4] = [EntityType::QUEEN, EntityType::ROOK, EntityType::BISHOP, EntityType::KNIGHT];
+
+impl Move {
+    /// A bare move without flags, `create_move` fills them in from a board
+    pub fn new(from: Position, to: Position) -> Self {
+        Self { from, to, promotion: None, flags: MoveFlags::default() }
+    }
+
+    pub fn with_promotion(from: Position, to: Position, promotion: EntityType) -> Self {
+        Self { from, to, promotion: Some(promotion), flags: MoveFlags::default() }
+    }
+}
+
+/// Why a move was turned down by `validate_move`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveError {
+    NoPiece,
+    GameOver,
+    NotYourTurn,
+    OwnPieceOnTarget,
+    PathBlocked,
+    IllegalPieceMove, // the piece doesn't move like that
+    CastlingNotAllowed,
+    InvalidPromotion,
+    LeavesKingInCheck,
+}
 
-    (dx as usize, dy as usize)
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::NoPiece => write!(f, "there is no piece on that square"),
+            MoveError::GameOver => write!(f, "the game is already over"),
+            MoveError::NotYourTurn => write!(f, "it is not your turn"),
+            MoveError::OwnPieceOnTarget => write!(f, "your own piece is on that square"),
+            MoveError::PathBlocked => write!(f, "another piece is in the way"),
+            MoveError::IllegalPieceMove => write!(f, "that piece can't move like that"),
+            MoveError::CastlingNotAllowed => write!(f, "castling isn't allowed right now"),
+            MoveError::InvalidPromotion => write!(f, "pawns promote to a queen, rook, bishop or knight on the last rank only"),
+            MoveError::LeavesKingInCheck => write!(f, "that would leave your king in check"),
+        }
+    }
 }
 
-pub fn validate(board: &Board, from: Vec2, to:Vec2, piece: GameEntity) -> bool {
-    let (dx, dy) = d(from, to);
+impl std::error::Error for MoveError {}
 
+/// What a move that went through did
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveOutcome {
+    pub mv: Move, // with its flags filled in
+    pub captured: Option<GameEntity>,
+    pub is_check: bool, // the other side is now in check
+    pub game: GameOutcome,
+}
+
+/// Everything `Board::unmake_move` needs to put the board back the way it was before a move
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UndoInfo {
+    pub mv: Move,
+    pub moved: GameEntity, // the piece as it stood on `from`, before any promotion
+    pub captured: Option<GameEntity>,
+    pub castling: CastlingRights,
+    pub en_passant: Option<Position>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    pub hash: u64,
+    pub is_check: bool,
+    pub is_checkmate: bool,
+    pub is_stalemate: bool,
+    pub who_in_check: TeamLoyalty,
+}
+
+/// Builds the move of the piece on `from` to `to` with its flags worked out from `board`
+pub fn create_move(board: &Board, from: Position, to: Position, promotion: Option<EntityType>) -> Move {
+    let mut mv = Move { from, to, promotion, flags: MoveFlags::default() };
+    let piece = match board.entity_at(from) {
+        Some(ent) => *ent,
+        None => return mv,
+    };
+
+    mv.flags.capture = board.entity_at(to).is_some();
     match piece.entity_type {
         EntityType::PAWN => {
-            if piece.first_move { 
-                return (dx == 0 && (dy == 1 || dy == 2) && board.entity_at(to).is_none()) || (dx == 1 && dy == 1 && board.entity_at(to).is_some());
-            } else {
-                return (dx == 0 && dy == 1 && board.entity_at(to).is_none()) || (dx == 1 && dy == 1 && board.entity_at(to).is_some());    
+            if from.x != to.x && !mv.flags.capture && board.en_passant == Some(to) {
+                mv.flags.en_passant = true;
+                mv.flags.capture = true;
             }
-        }
-        EntityType::ROOK => {
-            return (dx == 0 && dy != 0) || (dx != 0 && dy == 0);
+            mv.flags.double_push = (to.y - from.y).abs() == 2;
         },
-        EntityType::BISHOP => {
-            return dx == dy && dx != 0;
-        },
-        EntityType::KNIGHT => {
-            return (dx == 2 && dy == 1) || (dx == 1 && dy == 2);
-        },
-        EntityType::QUEEN => {
-            return (dx == dy && dx != 0) || (dx == 0 && dy != 0) || (dx != 0 && dy == 0);
+        EntityType::KING => mv.flags.castle = (to.x - from.x).abs() == 2,
+        _ => {},
+    }
+    mv
+}
+
+impl TeamLoyalty {
+    pub fn opposite(&self) -> TeamLoyalty {
+        match self {
+            TeamLoyalty::WHITE => TeamLoyalty::BLACK,
+            TeamLoyalty::BLACK => TeamLoyalty::WHITE,
+            TeamLoyalty::NONE => TeamLoyalty::NONE,
+        }
+    }
+
+    // row 0 is black's back rank so white pawns walk "up" the rows
+    fn pawn_direction(&self) -> i32 {
+        if *self == TeamLoyalty::WHITE { -1 } else { 1 }
+    }
+
+    fn pawn_start_row(&self) -> i32 {
+        if *self == TeamLoyalty::WHITE { 6 } else { 1 }
+    }
+
+    fn back_row(&self) -> i32 {
+        if *self == TeamLoyalty::WHITE { 7 } else { 0 }
+    }
+}
+
+fn on_board(x: i32, y: i32) -> bool {
+    (0..8).contains(&x) && (0..8).contains(&y)
+}
+
+/// "e4" style name of a board position (row 0 is the 8th rank), empty if it is off the board
+pub fn position_to_algebraic(pos: Position) -> String {
+    Square::from_position(pos).map(|square| square.to_string()).unwrap_or_default()
+}
+
+pub fn algebraic_to_position(name: &str) -> Option<Position> {
+    name.parse::<Square>().ok().map(|square| square.position())
+}
+
+/// Returns true if any piece of `by` could capture on `pos`
+fn is_square_attacked(board: &Board, pos: Position, by: TeamLoyalty) -> bool {
+    board.bitboards.is_attacked(square_index(pos), by)
+}
+
+fn find_king(board: &Board, team: TeamLoyalty) -> Option<Position> {
+    board.bitboards.king_square(team).map(index_position)
+}
+
+// a pawn landing on either back rank has to pick what it becomes
+fn push_pawn_move(board: &Board, moves: &mut Vec<Move>, from: Position, to: Position) {
+    if to.y == 0 || to.y == 7 {
+        for promotion in PROMOTION_CHOICES {
+            moves.push(create_move(board, from, to, Some(promotion)));
+        }
+    } else {
+        moves.push(create_move(board, from, to, None));
+    }
+}
+
+/// Every move the piece on `from` could make ignoring whether it leaves its own king in check
+fn pseudo_legal_moves_from(board: &Board, from: Position) -> Vec<Move> {
+    let mut moves: Vec<Move> = Vec::new();
+    let piece = match board.entity_at(from) {
+        Some(ent) => *ent,
+        None => return moves,
+    };
+
+    let square = square_index(from);
+    let bitboards = &board.bitboards;
+    let own = bitboards.team(piece.team_id);
+    let enemy = bitboards.team(piece.team_id.opposite());
+
+    let targets = match piece.entity_type {
+        EntityType::PAWN => {
+            let mut targets = pawn_attacks(piece.team_id, square) & enemy;
+            if let Some(en_passant) = board.en_passant {
+                targets |= pawn_attacks(piece.team_id, square) & square_bit(en_passant);
+            }
+
+            let dir = piece.team_id.pawn_direction();
+            let one = Vec2 { x: from.x, y: from.y + dir };
+            if on_board(one.x, one.y) && bitboards.occupied & square_bit(one) == 0 {
+                targets |= square_bit(one);
+                let two = Vec2 { x: from.x, y: from.y + dir * 2 };
+                if from.y == piece.team_id.pawn_start_row() && bitboards.occupied & square_bit(two) == 0 {
+                    targets |= square_bit(two);
+                }
+            }
+
+            for to in Squares(targets) {
+                push_pawn_move(board, &mut moves, from, index_position(to));
+            }
+            return moves;
         },
+        EntityType::KNIGHT => knight_attacks(square),
         EntityType::KING => {
-            return dx == 1 || dy == 1;
+            moves.extend(castling_moves(board, from, piece.team_id));
+            king_attacks(square)
         },
-        _ => false
+        EntityType::ROOK => rook_attacks(square, bitboards.occupied),
+        EntityType::BISHOP => bishop_attacks(square, bitboards.occupied),
+        EntityType::QUEEN => queen_attacks(square, bitboards.occupied),
+        EntityType::NOTSET => 0,
+    };
+
+    for to in Squares(targets & !own) {
+        moves.push(create_move(board, from, index_position(to), None));
     }
+    moves
 }
 
-fn validate_horizontal(board: &Board, start: Vec2, end: Vec2, piece: GameEntity) -> bool {
-    if start.x - end.x == 0 { return true; } // didn't move horizonally
-    // Check X
-    for x in start.x..end.x {
-        let new_pos = Vec2 {
-            x,
-            y: start.y,
-        };
-        let ent_at = board.entity_at(new_pos);
-        if ent_at.is_some() && ent_at.unwrap() != piece{
-            println!("Horizontal: Colliding with {:?} at {:?}", ent_at, new_pos);
-            return ent_at.unwrap().team_id != piece.team_id;
-        }
+/// The king's two square castling moves, the landing square itself is checked by `is_legal_after`
+fn castling_moves(board: &Board, from: Position, team: TeamLoyalty) -> Vec<Move> {
+    let mut moves: Vec<Move> = Vec::new();
+    let row = team.back_row();
+    if from.x != 4 || from.y != row || is_square_attacked(board, from, team.opposite()) {
+        return moves;
     }
-    true
+
+    let is_empty = |x: i32| board.entity_at(Vec2 { x, y: row }).is_none();
+    let is_safe = |x: i32| !is_square_attacked(board, Vec2 { x, y: row }, team.opposite());
+    // the rights alone can't be trusted, a FEN can hand them out with no rook in the corner
+    let has_rook = |x: i32| board.bitboards.piece(team, EntityType::ROOK) & square_bit(Vec2 { x, y: row }) != 0;
+
+    if board.castling.king_side(team) && has_rook(7) && is_empty(5) && is_empty(6) && is_safe(5) {
+        moves.push(create_move(board, from, Vec2 { x: 6, y: row }, None));
+    }
+    if board.castling.queen_side(team) && has_rook(0) && is_empty(3) && is_empty(2) && is_empty(1) && is_safe(3) {
+        moves.push(create_move(board, from, Vec2 { x: 2, y: row }, None));
+    }
+    moves
 }
 
-fn validate_vertical(board: &Board, start: Vec2, end: Vec2, piece: GameEntity) -> bool {
-    // Check Y
-    if start.y - end.y == 0 { return true; } // didn't move vertically
-    for y in start.y..end.y {
-        let new_pos = Vec2 {
-            x: start.x,
-            y,
-        };
-        let ent_at = board.entity_at(new_pos);
-        if ent_at.is_some() && ent_at.unwrap() != piece{
-            println!("Vertical: Colliding with {:?} at {:?}", ent_at, new_pos);
-            return ent_at.unwrap().team_id != piece.team_id;
-        }
+// random keys for zobrist hashing, made at compile time with splitmix64 so every build agrees
+struct ZobristKeys {
+    pieces: [[[u64; 64]; 6]; 2], // [team][piece][square]
+    black_to_move: u64,
+    castling: [u64; 4], // K Q k q
+    en_passant_file: [u64; 8],
+}
+
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_zobrist_keys() -> ZobristKeys {
+    let mut keys = ZobristKeys { pieces: [[[0; 64]; 6]; 2], black_to_move: 0, castling: [0; 4], en_passant_file: [0; 8] };
+    let mut state = 0x2545F4914F6CDD1D;
+    let mut key;
+
+    let mut i = 0;
+    while i < 2 * 6 * 64 {
+        (state, key) = splitmix64(state);
+        keys.pieces[i / 384][(i / 64) % 6][i % 64] = key;
+        i += 1;
+    }
+    (state, key) = splitmix64(state);
+    keys.black_to_move = key;
+    i = 0;
+    while i < 4 {
+        (state, key) = splitmix64(state);
+        keys.castling[i] = key;
+        i += 1;
     }
-    true
+    i = 0;
+    while i < 8 {
+        (state, key) = splitmix64(state);
+        keys.en_passant_file[i] = key;
+        i += 1;
+    }
+    keys
+}
+
+static ZOBRIST: ZobristKeys = generate_zobrist_keys();
+
+fn piece_key(ent: &GameEntity, pos: Position) -> u64 {
+    match (team_index(ent.team_id), piece_index(ent.entity_type)) {
+        (Some(team), Some(piece)) => ZOBRIST.pieces[team][piece][square_index(pos)],
+        _ => 0,
+    }
+}
+
+fn castling_key(castling: &CastlingRights) -> u64 {
+    let rights = [castling.white_king_side, castling.white_queen_side, castling.black_king_side, castling.black_queen_side];
+    rights.iter().zip(ZOBRIST.castling.iter())
+        .filter(|(has_right, _)| **has_right)
+        .fold(0, |hash, (_, key)| hash ^ key)
+}
+
+// the en passant file only goes into the hash when a pawn of the side to move is
+// beside the pawn that just jumped, otherwise the position plays out the same
+fn en_passant_key(board: &Board) -> u64 {
+    let target = match board.en_passant {
+        Some(target) => target,
+        None => return 0,
+    };
+    // our pawns that could take on the target are where an enemy pawn there would attack
+    let capturers = pawn_attacks(board.current_turn.opposite(), square_index(target))
+        & board.bitboards.piece(board.current_turn, EntityType::PAWN);
+    if capturers != 0 { ZOBRIST.en_passant_file[target.x as usize] } else { 0 }
 }
 
-fn validate_diagonal(board: &Board, start: Vec2, end: Vec2, piece: GameEntity) -> bool {
-    // triangles
-    if start.y - end.y == 0 || start.x - end.x == 0 { return false; } // return false if we don't move diagonally
-    
-    // issue when going down and left... yay { hopefully this fixes it}
-    let x_iter = create_range_vector(start.x, end.x);
-    let y_iter = create_range_vector(start.y, end.y);
+/// Moves the piece without any validation and hands the turn over
+fn apply_move(board: &mut Board, mv: Move) {
+    if board.entity_at(mv.from).is_none() { return; }
 
-    for y in y_iter {
-        let tx = x_iter.get((start.y-y) as usize);
-    
-        if tx.is_none() {
-            return true;
+    // take the old rights and en passant file out of the hash, the new ones go back in at the end
+    board.hash ^= castling_key(&board.castling) ^ en_passant_key(board);
+
+    let mut ent = board.take_piece(mv.from).unwrap();
+    board.take_piece(mv.to); // whatever was captured
+
+    if ent.entity_type == EntityType::PAWN || mv.flags.capture {
+        board.halfmove_clock = 0;
+    } else {
+        board.halfmove_clock += 1;
+    }
+    if ent.team_id == TeamLoyalty::BLACK {
+        board.fullmove_number += 1;
+    }
+
+    if ent.entity_type == EntityType::KING {
+        board.castling.clear_team(ent.team_id);
+
+        // castling, bring the rook over to the other side of the king
+        if mv.flags.castle {
+            let (rook_from, rook_to) = if mv.to.x > mv.from.x { (7, 5) } else { (0, 3) };
+            if let Some(mut rook) = board.take_piece(Vec2 { x: rook_from, y: mv.from.y }) {
+                rook.first_move = false;
+                board.put_piece(Vec2 { x: rook_to, y: mv.from.y }, rook);
+            }
         }
+    }
+    board.castling.clear_corner(mv.from);
+    board.castling.clear_corner(mv.to);
 
-        let x = *tx.unwrap();
+    board.en_passant = None;
+    if mv.flags.double_push {
+        // the square that was skipped over can be captured on for one ply
+        board.en_passant = Some(Vec2 { x: mv.from.x, y: (mv.from.y + mv.to.y) / 2 });
+    } else if mv.flags.en_passant {
+        // the captured pawn sits beside us, not on the target square
+        board.take_piece(Vec2 { x: mv.to.x, y: mv.from.y });
+    }
 
-        let new_pos = Vec2 {
-            y,
-            x
-        };
+    if let Some(promotion) = mv.promotion {
+        ent.entity_type = promotion;
+    }
 
-        let ent_at = board.entity_at(new_pos);
-        println!("{:?}", ent_at);
-        if ent_at.is_some() && ent_at.unwrap() != piece{
-            println!("Diagonal: Colliding with {:?} at {:?}", ent_at, new_pos);
+    ent.first_move = false;
+    board.put_piece(mv.to, ent);
+    board.current_turn = board.current_turn.opposite();
+    board.hash ^= ZOBRIST.black_to_move ^ castling_key(&board.castling) ^ en_passant_key(board);
+}
+
+/// Checks that playing `mv` does not leave the mover's own king attacked
+fn is_legal_after(board: &Board, mv: Move) -> bool {
+    let ent = match board.entity_at(mv.from) {
+        Some(ent) => *ent,
+        None => return false,
+    };
 
-            return ent_at.unwrap().team_id != piece.team_id;
+    // only the piece sets are needed to look for attacks, so play the move on a copy of those
+    let mut bitboards = board.bitboards;
+    bitboards.toggle(&ent, square_index(mv.from));
+    if let Some(captured) = board.entity_at(mv.to) {
+        bitboards.toggle(captured, square_index(mv.to));
+    }
+    if mv.flags.en_passant {
+        let captured_pos = Vec2 { x: mv.to.x, y: mv.from.y };
+        if let Some(captured) = board.entity_at(captured_pos) {
+            bitboards.toggle(captured, square_index(captured_pos));
+        }
+    }
+    if mv.flags.castle {
+        let (rook_from, rook_to) = if mv.to.x > mv.from.x { (7, 5) } else { (0, 3) };
+        if let Some(rook) = board.entity_at(Vec2 { x: rook_from, y: mv.from.y }) {
+            bitboards.toggle(rook, square_index(Vec2 { x: rook_from, y: mv.from.y }));
+            bitboards.toggle(rook, square_index(Vec2 { x: rook_to, y: mv.from.y }));
         }
     }
-    true
+    // a promoted pawn blocks the same as any other piece, so the new type doesn't matter here
+    bitboards.toggle(&ent, square_index(mv.to));
+
+    match bitboards.king_square(ent.team_id) {
+        Some(king) => !bitboards.is_attacked(king, ent.team_id.opposite()),
+        None => true, // custom setups without a king can't be in check
+    }
 }
 
-pub fn move_entity(board: &mut Board, original:Position, new_pos:Position, ignore_checks:bool) {
-    let tmp_ent = board.cells[original.y as usize][original.x as usize].occupier.clone();
+/// All legal moves of the piece on `from`, empty if it isn't that side's turn
+pub fn legal_moves_from(board: &Board, from: Position) -> Vec<Move> {
+    match board.entity_at(from) {
+        Some(ent) if ent.team_id == board.current_turn => {},
+        _ => return Vec::new(),
+    }
 
-    if tmp_ent.is_none() { return; }
-    let mut ent = tmp_ent.unwrap();
-    
-    let mut is_legal =   validate(board, original, new_pos, ent);
+    pseudo_legal_moves_from(board, from)
+        .into_iter()
+        .filter(|mv| is_legal_after(board, *mv))
+        .collect()
+}
 
-    if ent.entity_type != EntityType::KNIGHT && is_legal {
-        is_legal = if validate_diagonal(board, original, new_pos, ent) {
-            true
-        } else { 
-            if !validate_horizontal(board, original, new_pos, ent) {
-                println!("chess_engine::move_entity() -> Horizontal False");
-                false
-            } else if !validate_vertical(board, original, new_pos, ent) {
-                println!("chess_engine::move_entity() -> Vertical False");
-                false
-            } else {
-                true
-            }
-        };
+/// True if moving the piece on `from` to `to` needs a promotion choice
+pub fn is_promotion_move(board: &Board, from: Position, to: Position) -> bool {
+    legal_moves_from(board, from).iter().any(|mv| mv.to == to && mv.promotion.is_some())
+}
+
+/// All legal moves for the side whose turn it is
+pub fn legal_moves(board: &Board) -> Vec<Move> {
+    let mut moves: Vec<Move> = Vec::new();
+    for square in Squares(board.bitboards.team(board.current_turn)) {
+        moves.extend(legal_moves_from(board, index_position(square)));
     }
+    moves
+}
 
-    let tmp_ent_at = board.cells[new_pos.y as usize][new_pos.x as usize].occupier.clone();
+/// Rough material worth of a piece in centipawns, the king is priceless so it counts for nothing
+pub fn piece_value(entity_type: EntityType) -> i32 {
+    match entity_type {
+        EntityType::PAWN => 100,
+        EntityType::KNIGHT => 300,
+        EntityType::BISHOP => 300,
+        EntityType::ROOK => 500,
+        EntityType::QUEEN => 900,
+        EntityType::KING | EntityType::NOTSET => 0,
+    }
+}
+
+/// Every square `by` attacks, squares with their own pieces on count too (those are defended).
+/// Walk the set with `Square::in_bitboard`.
+pub fn attacked_squares(board: &Board, by: TeamLoyalty) -> Bitboard {
+    board.bitboards.attacks(by)
+}
 
-    if tmp_ent_at.is_some() {
-        // check if it's a king
-        let check_ent = tmp_ent_at.unwrap();
-        if check_ent.entity_type == EntityType::KING {
-            // game over as the king is dead
-            board.is_checkmate = true;
+/// How many pieces of `by` hit each square, indexed by `Square::index`
+pub fn attack_counts(board: &Board, by: TeamLoyalty) -> [u8; 64] {
+    let mut counts = [0; 64];
+    for (square, ent) in board.pieces().filter(|(_, ent)| ent.team_id == by) {
+        for target in Squares(board.bitboards.piece_attacks(by, ent.entity_type, square.index())) {
+            counts[target] += 1;
         }
     }
+    counts
+}
+
+pub fn is_attacked(board: &Board, square: Square, by: TeamLoyalty) -> bool {
+    board.bitboards.is_attacked(square.index(), by)
+}
+
+/// The pieces of `by` that could capture on `square`
+pub fn attackers_of(board: &Board, square: Square, by: TeamLoyalty) -> Bitboard {
+    board.bitboards.attackers_to(square.index(), by)
+}
+
+/// Pieces of `team` pinned to their own king
+pub fn pinned_pieces(board: &Board, team: TeamLoyalty) -> Bitboard {
+    board.bitboards.pinned(team)
+}
+
+/// Pieces of `team` (the king aside) the other side could win, either nothing defends them
+/// or something cheaper attacks them
+pub fn hanging_pieces(board: &Board, team: TeamLoyalty) -> Bitboard {
+    let enemy = team.opposite();
+    let mut hanging = 0;
+    for (square, ent) in board.pieces().filter(|(_, ent)| ent.team_id == team && ent.entity_type != EntityType::KING) {
+        let attackers = attackers_of(board, square, enemy);
+        if attackers == 0 {
+            continue;
+        }
+        if attackers_of(board, square, team) == 0 {
+            hanging |= square.bit();
+            continue;
+        }
+
+        // the king can't take a defended piece, so it doesn't count here
+        let cheapest_attacker = Square::in_bitboard(attackers)
+            .filter_map(|attacker| board.piece_at(attacker))
+            .filter(|attacker| attacker.entity_type != EntityType::KING)
+            .map(|attacker| piece_value(attacker.entity_type))
+            .min();
+        if matches!(cheapest_attacker, Some(value) if value < piece_value(ent.entity_type)) {
+            hanging |= square.bit();
+        }
+    }
+    hanging
+}
+
+/// Recomputes `is_check`, `who_in_check`, `is_checkmate` and `is_stalemate` for the side to move
+pub fn update_check_state(board: &mut Board) {
+    let team = board.current_turn;
+    board.is_check = match find_king(board, team) {
+        Some(king_pos) => is_square_attacked(board, king_pos, team.opposite()),
+        None => false,
+    };
+    board.who_in_check = if board.is_check { team } else { TeamLoyalty::NONE };
+
+    let has_moves = !legal_moves(board).is_empty();
+    board.is_checkmate = board.is_check && !has_moves;
+    board.is_stalemate = !board.is_check && !has_moves;
+}
 
-    if !is_legal {
-        println!("Illegal Move!");
-        return;
+/// Works out why `mv` can't be played, or hands it back with its flags filled in if it can
+pub fn validate_move(board: &Board, mv: Move) -> Result<Move, MoveError> {
+    let piece = match board.entity_at(mv.from) {
+        Some(ent) => *ent,
+        None => return Err(MoveError::NoPiece),
+    };
+    if board.outcome().is_terminal() {
+        return Err(MoveError::GameOver);
+    }
+    if piece.team_id != board.current_turn {
+        return Err(MoveError::NotYourTurn);
+    }
+    if matches!(board.entity_at(mv.to), Some(ent) if ent.team_id == piece.team_id) {
+        return Err(MoveError::OwnPieceOnTarget);
     }
 
-    if ent.first_move {
-        ent.first_move = false;
+    let mv = create_move(board, mv.from, mv.to, mv.promotion);
+    let legal = legal_moves_from(board, mv.from);
+    if legal.contains(&mv) {
+        return Ok(mv);
     }
 
-    board.cells[new_pos.y as usize][new_pos.x as usize].update(ent);
-    board.cells[original.y as usize][original.x as usize].make_empty();
+    // the right squares but the wrong (or a missing) piece to promote to
+    if legal.iter().any(|other| other.to == mv.to) {
+        return Err(MoveError::InvalidPromotion);
+    }
+    if pseudo_legal_moves_from(board, mv.from).iter().any(|other| other.to == mv.to) {
+        return Err(MoveError::LeavesKingInCheck);
+    }
+    if mv.flags.castle && mv.from.y == piece.team_id.back_row() && mv.to.y == mv.from.y {
+        return Err(MoveError::CastlingNotAllowed);
+    }
 
-    if board.is_checkmate { 
-        return;
+    // could the piece get there on an empty board? then something is standing in the way
+    let (from, to) = (square_index(mv.from), square_bit(mv.to));
+    let reachable = match piece.entity_type {
+        EntityType::PAWN => {
+            let dir = piece.team_id.pawn_direction();
+            let steps = if mv.from.y == piece.team_id.pawn_start_row() { 2 } else { 1 };
+            mv.to.x == mv.from.x && (1..=steps).any(|step| mv.to.y == mv.from.y + dir * step)
+        },
+        EntityType::ROOK => rook_attacks(from, 0) & to != 0,
+        EntityType::BISHOP => bishop_attacks(from, 0) & to != 0,
+        EntityType::QUEEN => queen_attacks(from, 0) & to != 0,
+        _ => false, // knights and kings jump, nothing can block them
+    };
+    if reachable {
+        Err(MoveError::PathBlocked)
+    } else {
+        Err(MoveError::IllegalPieceMove)
     }
+}
+
+/// Plays `mv` if it is legal, `ignore_checks` skips everything but there being a piece to move
+/// (handy for replaying games that went on past a claimable draw). The flags are recomputed from the board either way.
+pub fn move_entity(board: &mut Board, mv: Move, ignore_checks:bool) -> Result<MoveOutcome, MoveError> {
+    let mv = if ignore_checks {
+        if board.entity_at(mv.from).is_none() { return Err(MoveError::NoPiece); }
 
-    board.current_turn = if board.current_turn == TeamLoyalty::WHITE {
-        TeamLoyalty::BLACK
+        create_move(board, mv.from, mv.to, mv.promotion)
     } else {
-        TeamLoyalty::WHITE
+        validate_move(board, mv)?
     };
+
+    let undo = board.make_move(mv);
+    Ok(MoveOutcome {
+        mv,
+        captured: undo.captured,
+        is_check: board.is_check,
+        game: board.outcome(),
+    })
+}
+
+fn perft_nodes(board: &mut Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = legal_moves(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for mv in moves {
+        let undo = board.make_move_quiet(mv);
+        nodes += perft_nodes(board, depth - 1);
+        board.unmake_move(undo);
+    }
+    nodes
 }
 
 impl Board {
 
     /* For Debugging  */
     pub fn to_string(&self) -> String {
-        let mut out = "- 0 1 2 3 4 5 6 7\n0".to_owned();
-        let mut row_i = 1;
-        for row in &self.cells {
-            for column in row {
-                out += &(" ".to_string() + &column.cell_fen_repr.to_string());
+        let mut out = String::new();
+        for rank in (0..8).rev() {
+            out.push((b'1' + rank) as char);
+            for square in Square::on_rank(rank) {
+                out.push(' ');
+                let cell = self.at(square.position());
+                out.push(if cell.is_occupied { cell.cell_fen_repr } else { '.' });
             }
-            out += &("\n".to_owned() + &row_i.to_string());
-            row_i += 1;
+            out.push('\n');
         }
+        out += "  a b c d e f g h";
         return out;
     }
 
+    // every piece change goes through these two so the cells, bitboards and hash stay in step
+    fn put_piece(&mut self, pos: Position, ent: GameEntity) {
+        self.bitboards.toggle(&ent, square_index(pos));
+        self.hash ^= piece_key(&ent, pos);
+        self.cells[pos.y as usize][pos.x as usize].update(ent);
+    }
+
+    fn take_piece(&mut self, pos: Position) -> Option<GameEntity> {
+        let ent = self.cells[pos.y as usize][pos.x as usize].make_empty()?;
+        self.bitboards.toggle(&ent, square_index(pos));
+        self.hash ^= piece_key(&ent, pos);
+        Some(ent)
+    }
+
+    /// Plays `mv` (flags as `create_move` works them out) without checking it is legal, returns
+    /// what is needed to take it back again
+    pub fn make_move(&mut self, mv: Move) -> UndoInfo {
+        let undo = self.make_move_quiet(mv);
+        update_check_state(self);
+        undo
+    }
+
+    // make_move without working out check and mate afterwards, for walking the move tree
+    // where only the pieces matter (the check fields are left as they were)
+    pub(crate) fn make_move_quiet(&mut self, mv: Move) -> UndoInfo {
+        let captured_pos = if mv.flags.en_passant { Vec2 { x: mv.to.x, y: mv.from.y } } else { mv.to };
+        let undo = UndoInfo {
+            mv,
+            moved: self.entity_at(mv.from).unwrap_or_default(),
+            captured: *self.entity_at(captured_pos),
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: self.hash,
+            is_check: self.is_check,
+            is_checkmate: self.is_checkmate,
+            is_stalemate: self.is_stalemate,
+            who_in_check: self.who_in_check,
+        };
+
+        apply_move(self, mv);
+        self.position_history.push(self.hash);
+        self.move_history.push(undo);
+        undo
+    }
+
+    /// Takes back the move `undo` came from, it has to be the last move made on this board
+    pub fn unmake_move(&mut self, undo: UndoInfo) {
+        let mv = undo.mv;
+
+        self.take_piece(mv.to);
+        self.put_piece(mv.from, undo.moved);
+        if mv.flags.castle {
+            let (rook_from, rook_to) = if mv.to.x > mv.from.x { (7, 5) } else { (0, 3) };
+            if let Some(mut rook) = self.take_piece(Vec2 { x: rook_to, y: mv.from.y }) {
+                rook.first_move = true; // castling needs the rook to not have moved yet
+                self.put_piece(Vec2 { x: rook_from, y: mv.from.y }, rook);
+            }
+        }
+        if let Some(captured) = undo.captured {
+            let captured_pos = if mv.flags.en_passant { Vec2 { x: mv.to.x, y: mv.from.y } } else { mv.to };
+            self.put_piece(captured_pos, captured);
+        }
+
+        self.current_turn = undo.moved.team_id;
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.hash = undo.hash;
+        self.is_check = undo.is_check;
+        self.is_checkmate = undo.is_checkmate;
+        self.is_stalemate = undo.is_stalemate;
+        self.who_in_check = undo.who_in_check;
+
+        self.position_history.pop();
+        self.move_history.pop();
+    }
+
+    /// Counts the leaf nodes of the legal move tree `depth` plies deep, the standard way to check move generation
+    pub fn perft(&self, depth: u32) -> u64 {
+        let mut board = self.clone();
+        perft_nodes(&mut board, depth)
+    }
+
+    /// `perft` split up by the first move, for tracking down which move a wrong count comes from
+    pub fn perft_divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+        let mut board = self.clone();
+        legal_moves(self).into_iter().map(|mv| {
+            let undo = board.make_move_quiet(mv);
+            let nodes = perft_nodes(&mut board, depth - 1);
+            board.unmake_move(undo);
+            (mv, nodes)
+        }).collect()
+    }
+
+    /// Undoes the last move made on this board, if there is one
+    pub fn takeback(&mut self) -> Option<Move> {
+        let undo = *self.move_history.last()?;
+        self.unmake_move(undo);
+        Some(undo.mv)
+    }
+
     pub fn at(&self, pos: Position) -> &Cell {
         return &self.cells[pos.y as usize][pos.x as usize];
     }
@@ -395,48 +1217,507 @@ impl Board {
         let turn = if self.current_turn == TeamLoyalty::WHITE { "w" } else { "b" };
         out += " ";
         out += turn;
-        
+
+        out += " ";
+        out += &self.castling_fen();
+
+        out += " ";
+        out += &match self.en_passant {
+            Some(pos) => position_to_algebraic(pos),
+            None => "-".to_owned(),
+        };
+
+        out += &format!(" {} {}", self.halfmove_clock, self.fullmove_number);
 
         return out;
     }
 
+    fn castling_fen(&self) -> String {
+        let mut out = String::new();
+        if self.castling.white_king_side { out.push('K'); }
+        if self.castling.white_queen_side { out.push('Q'); }
+        if self.castling.black_king_side { out.push('k'); }
+        if self.castling.black_queen_side { out.push('q'); }
+        if out.is_empty() { out.push('-'); }
+        out
+    }
+
     pub fn update_turn(&mut self, fen:String) {
-        self.current_turn = if fen.chars().last().unwrap() == 'w' { TeamLoyalty::WHITE } else { TeamLoyalty::BLACK };
+        self.current_turn = if fen.split_whitespace().nth(1) == Some("w") { TeamLoyalty::WHITE } else { TeamLoyalty::BLACK };
+        self.hash = self.compute_hash();
     }
 
+    /// Parses a FEN string, panicking if it is malformed. Use `from_fen` for anything a user typed.
     pub fn create_board(fen: String) -> Self {
-        let mut cells: Vec<Vec<Cell>> = Vec::new();
-        let current_turn: TeamLoyalty = if fen.chars().last().unwrap() == 'w' { TeamLoyalty::WHITE } else { TeamLoyalty::BLACK };
+        match Self::from_fen(&fen) {
+            Ok(board) => board,
+            Err(err) => panic!("chess_engine::Board::create_board() -> {} in '{}'", err, fen),
+        }
+    }
 
-        // the first
-        cells.push(Vec::new());
+    /// Parses a FEN string. Only the placement and side to move are required, the remaining
+    /// fields fall back to what the placement allows, no en passant, and clocks of 0 and 1.
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.is_empty() {
+            return Err(FenError::Empty);
+        }
+        if fields.len() > 6 {
+            return Err(FenError::TooManyFields(fields.len()));
+        }
 
-        for fen_char in fen.chars() {
-            if fen_char == '/' {
-                cells.push(Vec::new());
-                continue;
-            }
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()));
+        }
 
-            if fen_char == ' ' {
-                break;
-            }
-            if fen_char < (57 as char) && fen_char > (47 as char)  {
-                let count = (fen_char as i32) - 47;
-                for _ in 0..count-1 {
-                    cells.last_mut().unwrap().push(Cell::default());
+        let mut cells: Vec<Vec<Cell>> = Vec::new();
+        for (row_i, rank) in ranks.iter().enumerate() {
+            let mut row: Vec<Cell> = Vec::new();
+            for fen_char in rank.chars() {
+                match fen_char {
+                    '1'..='8' => {
+                        let count = fen_char as usize - '0' as usize;
+                        for _ in 0..count {
+                            row.push(Cell::default());
+                        }
+                    },
+                    _ if get_entity_type(fen_char) != EntityType::NOTSET => row.push(create_cell(fen_char)),
+                    _ => return Err(FenError::InvalidPiece(fen_char)),
                 }
-            } else {
-                let data = create_cell(fen_char);
-                cells.last_mut().unwrap().push(data);
             }
+            if row.len() != 8 {
+                return Err(FenError::WrongRankLength { rank: 8 - row_i, length: row.len() });
+            }
+            cells.push(row);
         }
-        Self {
+
+        let current_turn = match fields.get(1) {
+            Some(&"w") => TeamLoyalty::WHITE,
+            Some(&"b") => TeamLoyalty::BLACK,
+            Some(other) => return Err(FenError::InvalidSideToMove(other.to_string())),
+            None => return Err(FenError::MissingSideToMove),
+        };
+
+        let mut board = Self {
+            bitboards: Bitboards::from_cells(&cells),
             cells,
             current_turn,
+            castling: CastlingRights::default(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
             is_check: false,
             is_checkmate: false,
+            is_stalemate: false,
             who_in_check: TeamLoyalty::NONE,
+            hash: 0,
+            position_history: Vec::new(),
+            move_history: Vec::new(),
+        };
+
+        board.castling = match fields.get(2) {
+            Some(&"-") => CastlingRights::default(),
+            Some(rights) => {
+                let mut castling = CastlingRights::default();
+                for right in rights.chars() {
+                    let flag = match right {
+                        'K' => &mut castling.white_king_side,
+                        'Q' => &mut castling.white_queen_side,
+                        'k' => &mut castling.black_king_side,
+                        'q' => &mut castling.black_queen_side,
+                        _ => return Err(FenError::InvalidCastling(rights.to_string())),
+                    };
+                    if *flag {
+                        return Err(FenError::InvalidCastling(rights.to_string()));
+                    }
+                    *flag = true;
+                }
+                castling
+            },
+            None => board.castling_from_placement(),
+        };
+
+        board.en_passant = match fields.get(3) {
+            Some(&"-") | None => None,
+            Some(square) => match algebraic_to_position(square) {
+                Some(pos) => Some(pos),
+                None => return Err(FenError::InvalidEnPassant(square.to_string())),
+            },
+        };
+
+        if let Some(clock) = fields.get(4) {
+            board.halfmove_clock = clock.parse().map_err(|_| FenError::InvalidHalfmoveClock(clock.to_string()))?;
+        }
+        if let Some(number) = fields.get(5) {
+            board.fullmove_number = match number.parse() {
+                Ok(n) if n > 0 => n,
+                _ => return Err(FenError::InvalidFullmoveNumber(number.to_string())),
+            };
+        }
+
+        board.hash = board.compute_hash();
+        update_check_state(&mut board);
+        board.position_history.push(board.hash);
+        Ok(board)
+    }
+
+    /// Zobrist key of the position worked out from scratch, `hash` should always match this
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = castling_key(&self.castling) ^ en_passant_key(self);
+        if self.current_turn == TeamLoyalty::BLACK {
+            hash ^= ZOBRIST.black_to_move;
+        }
+        for square in Squares(self.bitboards.occupied) {
+            if let Some(ent) = self.entity_at(index_position(square)) {
+                hash ^= piece_key(ent, index_position(square));
+            }
+        }
+        hash
+    }
+
+    /// How many times the current position has come up, including now
+    pub fn repetition_count(&self) -> usize {
+        match self.position_history.last() {
+            Some(current) => self.position_history.iter().filter(|hash| *hash == current).count(),
+            None => 1,
+        }
+    }
+
+    /// True when neither side has enough left to ever deliver mate
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut minors: Vec<(EntityType, i32)> = Vec::new(); // piece and square colour
+        for y in 0..8 {
+            for x in 0..8 {
+                if let Some(ent) = self.entity_at(Vec2 { x, y }) {
+                    match ent.entity_type {
+                        EntityType::KING => {},
+                        EntityType::BISHOP | EntityType::KNIGHT => minors.push((ent.entity_type, (x + y) % 2)),
+                        _ => return false,
+                    }
+                }
+            }
+        }
+
+        match minors.len() {
+            0 | 1 => true,
+            // any number of bishops all on one colour can't mate either
+            _ => minors.iter().all(|(entity_type, colour)| *entity_type == EntityType::BISHOP && *colour == minors[0].1),
+        }
+    }
+
+    /// Checkmate and stalemate first, then the fifty move rule, repetition and dead positions
+    pub fn outcome(&self) -> GameOutcome {
+        if self.is_checkmate {
+            GameOutcome::Checkmate(self.current_turn.opposite())
+        } else if self.is_stalemate {
+            GameOutcome::Stalemate
+        } else if self.halfmove_clock >= 100 {
+            GameOutcome::FiftyMoveRule
+        } else if self.repetition_count() >= 3 {
+            GameOutcome::ThreefoldRepetition
+        } else if self.is_insufficient_material() {
+            GameOutcome::InsufficientMaterial
+        } else {
+            GameOutcome::Ongoing
+        }
+    }
+
+    /// Rejects positions that can't come up in a real game, run this before starting from a user's FEN
+    pub fn validate_position(&self) -> Result<(), PositionError> {
+        for team in [TeamLoyalty::WHITE, TeamLoyalty::BLACK] {
+            let (mut kings, mut pawns, mut pieces) = (0, 0, 0);
+            for y in 0..8 {
+                for x in 0..8 {
+                    let pos = Vec2 { x, y };
+                    let ent = match self.entity_at(pos) {
+                        Some(ent) if ent.team_id == team => ent,
+                        _ => continue,
+                    };
+                    pieces += 1;
+                    match ent.entity_type {
+                        EntityType::KING => kings += 1,
+                        EntityType::PAWN => {
+                            pawns += 1;
+                            if y == 0 || y == 7 {
+                                return Err(PositionError::PawnOnBackRank(pos));
+                            }
+                        },
+                        _ => {},
+                    }
+                }
+            }
+            if kings == 0 { return Err(PositionError::MissingKing(team)); }
+            if kings > 1 { return Err(PositionError::TooManyKings(team)); }
+            if pawns > 8 { return Err(PositionError::TooManyPawns(team)); }
+            if pieces > 16 { return Err(PositionError::TooManyPieces(team)); }
+        }
+
+        let waiting = self.current_turn.opposite();
+        if let Some(king_pos) = find_king(self, waiting) {
+            if is_square_attacked(self, king_pos, self.current_turn) {
+                return Err(PositionError::OpponentInCheck);
+            }
         }
+
+        let allowed = self.castling_from_placement();
+        let castling = self.castling;
+        if (castling.white_king_side && !allowed.white_king_side)
+            || (castling.white_queen_side && !allowed.white_queen_side)
+            || (castling.black_king_side && !allowed.black_king_side)
+            || (castling.black_queen_side && !allowed.black_queen_side) {
+            return Err(PositionError::InvalidCastlingRights);
+        }
+
+        if let Some(target) = self.en_passant {
+            // the side that just moved pushed a pawn two squares through `target`
+            let pusher = self.current_turn.opposite();
+            let dir = pusher.pawn_direction();
+            let pushed_to = Vec2 { x: target.x, y: target.y + dir };
+            let pushed_from = Vec2 { x: target.x, y: target.y - dir };
+            let pawn_is_there = match self.entity_at(pushed_to) {
+                Some(ent) => ent.entity_type == EntityType::PAWN && ent.team_id == pusher,
+                None => false,
+            };
+            if pushed_from.y != pusher.pawn_start_row()
+                || !pawn_is_there
+                || self.entity_at(target).is_some()
+                || self.entity_at(pushed_from).is_some() {
+                return Err(PositionError::InvalidEnPassant(target));
+            }
+        }
+
+        Ok(())
     }
 
-}
\ No newline at end of file
+    // without a castling field assume anything still standing on its home square may castle
+    fn castling_from_placement(&self) -> CastlingRights {
+        let is_home = |x: i32, y: i32, entity_type: EntityType, team: TeamLoyalty| -> bool {
+            match self.entity_at(Vec2 { x, y }) {
+                Some(ent) => ent.entity_type == entity_type && ent.team_id == team,
+                None => false,
+            }
+        };
+        let white_king = is_home(4, 7, EntityType::KING, TeamLoyalty::WHITE);
+        let black_king = is_home(4, 0, EntityType::KING, TeamLoyalty::BLACK);
+
+        CastlingRights {
+            white_king_side: white_king && is_home(7, 7, EntityType::ROOK, TeamLoyalty::WHITE),
+            white_queen_side: white_king && is_home(0, 7, EntityType::ROOK, TeamLoyalty::WHITE),
+            black_king_side: black_king && is_home(7, 0, EntityType::ROOK, TeamLoyalty::BLACK),
+            black_queen_side: black_king && is_home(0, 0, EntityType::ROOK, TeamLoyalty::BLACK),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // published node counts, see https://www.chessprogramming.org/Perft_Results
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_4_MIRRORED: &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    fn assert_perft(fen: &str, counts: &[u64]) {
+        let board = Board::from_fen(fen).unwrap();
+        for (depth, expected) in counts.iter().enumerate() {
+            let depth = depth as u32 + 1;
+            assert_eq!(board.perft(depth), *expected, "perft({}) of {}", depth, fen);
+        }
+    }
+
+    #[test]
+    fn perft_start_position() {
+        assert_perft(DEFAULTFEN, &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        assert_perft(KIWIPETE, &[48, 2039, 97862]);
+    }
+
+    #[test]
+    fn perft_position_3() {
+        assert_perft(POSITION_3, &[14, 191, 2812, 43238, 674624]);
+    }
+
+    #[test]
+    fn perft_position_4() {
+        assert_perft(POSITION_4, &[6, 264, 9467]);
+        assert_perft(POSITION_4_MIRRORED, &[6, 264, 9467]);
+    }
+
+    #[test]
+    fn perft_position_5() {
+        assert_perft(POSITION_5, &[44, 1486, 62379]);
+    }
+
+    #[test]
+    fn perft_position_6() {
+        assert_perft(POSITION_6, &[46, 2079, 89890]);
+    }
+
+    // the deeper counts take a while in debug builds, run them with `cargo test --release -- --ignored`
+    #[test]
+    #[ignore]
+    fn perft_deep() {
+        assert_eq!(Board::from_fen(DEFAULTFEN).unwrap().perft(5), 4865609);
+        assert_eq!(Board::from_fen(KIWIPETE).unwrap().perft(4), 4085603);
+        assert_eq!(Board::from_fen(POSITION_4).unwrap().perft(4), 422333);
+        assert_eq!(Board::from_fen(POSITION_5).unwrap().perft(4), 2103487);
+        assert_eq!(Board::from_fen(POSITION_6).unwrap().perft(4), 3894594);
+    }
+
+    #[test]
+    fn perft_divide_adds_up() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let divide = board.perft_divide(2);
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), board.perft(2));
+    }
+
+    // a threefold repetition can be claimed but the game doesn't have to stop there
+    #[test]
+    fn fen_round_trips() {
+        for fen in [DEFAULTFEN, KIWIPETE, POSITION_3, POSITION_4, POSITION_4_MIRRORED, POSITION_5, POSITION_6] {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_fens() {
+        let error = |fen: &str| Board::from_fen(fen).unwrap_err();
+        assert_eq!(error("   "), FenError::Empty);
+        assert_eq!(error(&format!("{} extra", DEFAULTFEN)), FenError::TooManyFields(7));
+        assert_eq!(error("8/8/8/8/8/8/8 w"), FenError::WrongRankCount(7));
+        assert_eq!(error("7/8/8/8/8/8/8/8 w"), FenError::WrongRankLength { rank: 8, length: 7 });
+        assert_eq!(error("8/8/8/8/8/8/8/x7 w"), FenError::InvalidPiece('x'));
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3"), FenError::MissingSideToMove);
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 white"), FenError::InvalidSideToMove("white".to_owned()));
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w KK"), FenError::InvalidCastling("KK".to_owned()));
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w e9"), FenError::InvalidCastling("e9".to_owned()));
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - e9"), FenError::InvalidEnPassant("e9".to_owned()));
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - - -1 1"), FenError::InvalidHalfmoveClock("-1".to_owned()));
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - - 0 0"), FenError::InvalidFullmoveNumber("0".to_owned()));
+    }
+
+    #[test]
+    fn rejects_impossible_positions() {
+        let error = |fen: &str| Board::from_fen(fen).unwrap().validate_position().unwrap_err();
+        assert_eq!(error("4k3/8/8/8/8/8/8/8 w - - 0 1"), PositionError::MissingKing(TeamLoyalty::WHITE));
+        assert_eq!(error("4k1k1/8/8/8/8/8/8/4K3 w - - 0 1"), PositionError::TooManyKings(TeamLoyalty::BLACK));
+        assert_eq!(error("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - - 0 1"), PositionError::TooManyPawns(TeamLoyalty::WHITE));
+        assert_eq!(error("4k3/8/8/8/NNNNNNNN/NNNNNNNN/8/4K3 w - - 0 1"), PositionError::TooManyPieces(TeamLoyalty::WHITE));
+        assert_eq!(error("4k3/8/8/8/8/8/8/P3K3 w - - 0 1"), PositionError::PawnOnBackRank(Vec2 { x: 0, y: 7 }));
+        assert_eq!(error("4k3/8/8/8/8/8/8/4RK2 w - - 0 1"), PositionError::OpponentInCheck);
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w K - 0 1"), PositionError::InvalidCastlingRights);
+        assert_eq!(error("4k3/8/8/8/8/8/8/4K3 w - e6 0 1"), PositionError::InvalidEnPassant(Vec2 { x: 4, y: 2 }));
+
+        for fen in [DEFAULTFEN, KIWIPETE, POSITION_3, POSITION_4, POSITION_4_MIRRORED, POSITION_5, POSITION_6] {
+            assert_eq!(Board::from_fen(fen).unwrap().validate_position(), Ok(()), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn moves_after_repetition_are_still_played() {
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"];
+        for ignore_checks in [false, true] {
+            let mut board = Board::from_fen(DEFAULTFEN).unwrap();
+            for uci in shuffle {
+                move_entity(&mut board, Move::from_uci(uci).unwrap(), ignore_checks).unwrap();
+            }
+            assert_eq!(board.outcome(), GameOutcome::ThreefoldRepetition);
+            assert!(!board.outcome().is_terminal());
+            assert!(board.outcome().is_claimable());
+            assert_eq!(board.outcome().forced_end(), None);
+            assert_eq!(board.outcome().game_end(), Some(GameEnd::new(GameResult::Draw, Termination::Repetition)));
+
+            let played = move_entity(&mut board, Move::from_uci("e2e4").unwrap(), ignore_checks).unwrap();
+            assert_eq!(played.game, GameOutcome::Ongoing);
+            move_entity(&mut board, Move::from_uci("e7e5").unwrap(), ignore_checks).unwrap();
+        }
+    }
+
+    fn bitboard_of(squares: &[&str]) -> Bitboard {
+        squares.iter().map(|name| name.parse::<Square>().unwrap().bit()).fold(0, |all, bit| all | bit)
+    }
+
+    #[test]
+    fn finds_hanging_pieces() {
+        // the king can't take the knight since the rook covers it
+        let board = Board::from_fen("8/8/8/8/8/3k4/4N3/4R1K1 b - - 0 1").unwrap();
+        assert_eq!(hanging_pieces(&board, TeamLoyalty::WHITE), 0);
+
+        // without the rook it's free
+        let board = Board::from_fen("8/8/8/8/8/3k4/4N3/6K1 b - - 0 1").unwrap();
+        assert_eq!(hanging_pieces(&board, TeamLoyalty::WHITE), bitboard_of(&["e2"]));
+
+        // a defended rook is still lost to a pawn, the defended pawn isn't
+        let board = Board::from_fen("4k3/8/8/3p4/2R1P3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(hanging_pieces(&board, TeamLoyalty::WHITE), bitboard_of(&["c4"]));
+        assert_eq!(hanging_pieces(&board, TeamLoyalty::BLACK), bitboard_of(&["d5"]));
+    }
+
+    #[test]
+    fn finds_pinned_pieces() {
+        let board = Board::from_fen("4k3/8/8/b7/8/8/3N4/4K3 w - - 0 1").unwrap();
+        assert_eq!(pinned_pieces(&board, TeamLoyalty::WHITE), bitboard_of(&["d2"]));
+        assert_eq!(pinned_pieces(&board, TeamLoyalty::BLACK), 0);
+
+        // two pieces in the way means neither is pinned
+        let board = Board::from_fen("4k3/4r3/8/8/4P3/4N3/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(pinned_pieces(&board, TeamLoyalty::WHITE), 0);
+    }
+
+    #[test]
+    fn counts_attackers() {
+        let board = Board::from_fen(DEFAULTFEN).unwrap();
+        let counts = attack_counts(&board, TeamLoyalty::WHITE);
+        let count = |name: &str| counts[name.parse::<Square>().unwrap().index()];
+        assert_eq!(count("f3"), 3); // e2, g2 and the g1 knight
+        assert_eq!(count("d2"), 4); // b1 knight, bishop, queen and king
+        assert_eq!(count("e4"), 0);
+        assert_eq!(count("a1"), 0);
+        assert_eq!(counts.iter().map(|count| *count as u32).sum::<u32>(), 38);
+        assert_eq!(attack_counts(&board, TeamLoyalty::BLACK)[Square::new(5, 5).unwrap().index()], 3);
+    }
+
+    #[test]
+    fn no_castling_without_a_rook() {
+        // the rights say white can castle short but the corner holds a knight
+        let mut board = Board::from_fen("k7/8/8/8/8/8/8/4K2N w K - 0 1").unwrap();
+        assert!(legal_moves(&board).iter().all(|mv| !mv.flags.castle));
+        assert!(move_entity(&mut board, Move::new(Vec2 { x: 4, y: 7 }, Vec2 { x: 6, y: 7 }), false).is_err());
+
+        let board = Board::from_fen("k7/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        assert_eq!(legal_moves(&board).iter().filter(|mv| mv.flags.castle).count(), 2);
+    }
+
+    #[test]
+    fn no_moves_after_checkmate() {
+        let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let mate = move_entity(&mut board, Move::from_uci("d1d8").unwrap(), false).unwrap();
+        assert_eq!(mate.game, GameOutcome::Checkmate(TeamLoyalty::WHITE));
+        assert_eq!(mate.game.forced_end(), Some(GameEnd::new(GameResult::WhiteWins, Termination::Checkmate)));
+        assert_eq!(move_entity(&mut board, Move::from_uci("g8h8").unwrap(), false).unwrap_err(), MoveError::GameOver);
+    }
+
+    #[test]
+    fn perft_leaves_board_untouched() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let mut played = board.clone();
+        perft_nodes(&mut played, 3);
+        assert_eq!(played.to_fen(), board.to_fen());
+        assert_eq!(played.hash, board.hash);
+        assert_eq!(played.bitboards, board.bitboards);
+        assert_eq!(played.position_history, board.position_history);
+        assert_eq!(played.move_history.len(), board.move_history.len());
+        assert_eq!(played.halfmove_clock, board.halfmove_clock);
+        assert_eq!(played.fullmove_number, board.fullmove_number);
+    }
+}
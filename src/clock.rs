@@ -0,0 +1,415 @@
+use std::time::Duration;
+
+use crate::bitboard::team_index;
+use crate::chess_engine::{Board, EntityType, GameEnd, GameResult, TeamLoyalty, Termination};
+
+/// What a player gets back for each move they make
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bonus {
+    None,
+    Fischer(Duration), // added after every move
+    Bronstein(Duration), // whatever was used of the move, up to this much, is given back
+    SimpleDelay(Duration), // the clock only starts eating time once this has passed
+}
+
+/// One stretch of the game, `moves` of None means the rest of the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Period {
+    pub moves: Option<u32>,
+    pub time: Duration,
+    pub bonus: Bonus,
+}
+
+/// Why a time control string couldn't be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTimeControlError(pub String);
+
+impl std::fmt::Display for ParseTimeControlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' is not a time control", self.0)
+    }
+}
+
+impl std::error::Error for ParseTimeControlError {}
+
+/// How much time each side gets, written like the PGN TimeControl tag: "300+5", "40/5400+30:1800+30".
+/// Delays aren't in PGN so they get their own letters, "300d5" for a simple delay and "300b5" for Bronstein
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeControl {
+    pub periods: Vec<Period>,
+}
+
+impl TimeControl {
+    pub fn sudden_death(time: Duration) -> Self {
+        Self::with_bonus(time, Bonus::None)
+    }
+
+    pub fn fischer(time: Duration, increment: Duration) -> Self {
+        Self::with_bonus(time, Bonus::Fischer(increment))
+    }
+
+    pub fn bronstein(time: Duration, delay: Duration) -> Self {
+        Self::with_bonus(time, Bonus::Bronstein(delay))
+    }
+
+    pub fn simple_delay(time: Duration, delay: Duration) -> Self {
+        Self::with_bonus(time, Bonus::SimpleDelay(delay))
+    }
+
+    /// A single period for the whole game
+    pub fn with_bonus(time: Duration, bonus: Bonus) -> Self {
+        Self { periods: vec![Period { moves: None, time, bonus }] }
+    }
+
+    /// Several periods one after the other, the last one repeats if it has a move count
+    pub fn multi_period(periods: Vec<Period>) -> Self {
+        Self { periods }
+    }
+
+    // past the end the last period starts over
+    fn period(&self, index: usize) -> Option<&Period> {
+        self.periods.get(index).or(self.periods.last())
+    }
+}
+
+fn parse_seconds(text: &str) -> Option<Duration> {
+    let seconds: f64 = text.parse().ok()?;
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+fn format_seconds(time: Duration) -> String {
+    if time.subsec_millis() == 0 { time.as_secs().to_string() } else { format!("{}", time.as_secs_f64()) }
+}
+
+impl std::str::FromStr for Period {
+    type Err = ParseTimeControlError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = || ParseTimeControlError(text.to_owned());
+        let (moves, rest) = match text.split_once('/') {
+            Some((moves, rest)) => (Some(moves.parse::<u32>().ok().filter(|moves| *moves > 0).ok_or_else(error)?), rest),
+            None => (None, text),
+        };
+        let split = rest.find(['+', 'd', 'b']);
+        let (time, bonus) = match split {
+            Some(at) => {
+                let amount = parse_seconds(&rest[at + 1..]).ok_or_else(error)?;
+                let bonus = match &rest[at..at + 1] {
+                    "+" => Bonus::Fischer(amount),
+                    "d" => Bonus::SimpleDelay(amount),
+                    _ => Bonus::Bronstein(amount),
+                };
+                (&rest[..at], bonus)
+            },
+            None => (rest, Bonus::None),
+        };
+        Ok(Period { moves, time: parse_seconds(time).ok_or_else(error)?, bonus })
+    }
+}
+
+impl std::fmt::Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(moves) = self.moves {
+            write!(f, "{}/", moves)?;
+        }
+        write!(f, "{}", format_seconds(self.time))?;
+        match self.bonus {
+            Bonus::None => Ok(()),
+            Bonus::Fischer(amount) => write!(f, "+{}", format_seconds(amount)),
+            Bonus::SimpleDelay(amount) => write!(f, "d{}", format_seconds(amount)),
+            Bonus::Bronstein(amount) => write!(f, "b{}", format_seconds(amount)),
+        }
+    }
+}
+
+impl std::str::FromStr for TimeControl {
+    type Err = ParseTimeControlError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let periods = text.trim().split(':')
+            .map(|period| period.trim().parse::<Period>().map_err(|_| ParseTimeControlError(text.to_owned())))
+            .collect::<Result<Vec<Period>, ParseTimeControlError>>()?;
+        Ok(Self { periods })
+    }
+}
+
+impl std::fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let periods: Vec<String> = self.periods.iter().map(|period| period.to_string()).collect();
+        write!(f, "{}", periods.join(":"))
+    }
+}
+
+/// Both players' remaining time as the server sees it, "<white ms> <black ms>" on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSync {
+    pub white: Duration,
+    pub black: Duration,
+}
+
+impl ClockSync {
+    pub fn to_message(&self) -> String {
+        format!("{} {}", self.white.as_millis(), self.black.as_millis())
+    }
+
+    pub fn from_message(text: &str) -> Option<Self> {
+        let (white, black) = text.trim().split_once(' ')?;
+        Some(Self {
+            white: Duration::from_millis(white.trim().parse().ok()?),
+            black: Duration::from_millis(black.trim().parse().ok()?),
+        })
+    }
+}
+
+/// A two sided game clock, it doesn't keep time itself, `tick` gets told how much has passed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChessClock {
+    control: TimeControl,
+    remaining: [Duration; 2], // per team, see team_index
+    period: [usize; 2], // which period each side is in
+    moves_in_period: [u32; 2],
+    turn_time: Duration, // how long the side to move has been thinking
+    flagged: Option<TeamLoyalty>,
+}
+
+impl ChessClock {
+    pub fn new(control: TimeControl) -> Self {
+        let start = control.period(0).map(|period| period.time).unwrap_or_default();
+        Self {
+            control,
+            remaining: [start; 2],
+            period: [0; 2],
+            moves_in_period: [0; 2],
+            turn_time: Duration::ZERO,
+            flagged: None,
+        }
+    }
+
+    pub fn time_control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    pub fn remaining(&self, team: TeamLoyalty) -> Duration {
+        team_index(team).map(|team| self.remaining[team]).unwrap_or_default()
+    }
+
+    /// Who ran out of time, if anyone
+    pub fn flagged(&self) -> Option<TeamLoyalty> {
+        self.flagged
+    }
+
+    fn current_period(&self, team: usize) -> Option<Period> {
+        self.control.period(self.period[team]).copied()
+    }
+
+    /// Runs `team`'s clock for `elapsed`, returns who flagged if that made someone run out
+    pub fn tick(&mut self, team: TeamLoyalty, elapsed: Duration) -> Option<TeamLoyalty> {
+        let index = team_index(team)?;
+        if self.flagged.is_some() {
+            return self.flagged;
+        }
+
+        let mut used = elapsed;
+        if let Some(Period { bonus: Bonus::SimpleDelay(delay), .. }) = self.current_period(index) {
+            // only the part of the turn past the delay counts
+            let before = self.turn_time.saturating_sub(delay);
+            let after = (self.turn_time + elapsed).saturating_sub(delay);
+            used = after - before;
+        }
+        self.turn_time += elapsed;
+
+        self.remaining[index] = self.remaining[index].saturating_sub(used);
+        if self.remaining[index].is_zero() {
+            self.flagged = Some(team);
+        }
+        self.flagged
+    }
+
+    /// `team` finished their move, hands out the bonus and moves on to the next period when it's due
+    pub fn press(&mut self, team: TeamLoyalty) {
+        let index = match team_index(team) {
+            Some(index) => index,
+            None => return,
+        };
+        let period = match self.current_period(index) {
+            Some(period) => period,
+            None => return,
+        };
+        if self.flagged.is_some() {
+            return;
+        }
+
+        match period.bonus {
+            Bonus::Fischer(increment) => self.remaining[index] += increment,
+            Bonus::Bronstein(delay) => self.remaining[index] += self.turn_time.min(delay),
+            Bonus::None | Bonus::SimpleDelay(_) => {},
+        }
+
+        self.moves_in_period[index] += 1;
+        if Some(self.moves_in_period[index]) == period.moves {
+            self.period[index] += 1;
+            self.moves_in_period[index] = 0;
+            if let Some(next) = self.current_period(index) {
+                self.remaining[index] += next.time;
+            }
+        }
+        self.turn_time = Duration::ZERO;
+    }
+
+    /// Takes the server's word for how much time is left, it's the one keeping the real clock
+    pub fn sync(&mut self, sync: &ClockSync) {
+        self.remaining = [sync.white, sync.black];
+        self.flagged = if sync.white.is_zero() {
+            Some(TeamLoyalty::WHITE)
+        } else if sync.black.is_zero() {
+            Some(TeamLoyalty::BLACK)
+        } else {
+            None
+        };
+    }
+
+    pub fn to_sync(&self) -> ClockSync {
+        ClockSync { white: self.remaining[0], black: self.remaining[1] }
+    }
+}
+
+/// "1:02:03", "4:05", or "0:09.5" once it gets down to the last ten seconds
+pub fn format_clock(time: Duration) -> String {
+    let seconds = time.as_secs();
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+    } else if seconds >= 10 {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        format!("0:{:02}.{}", seconds, time.subsec_millis() / 100)
+    }
+}
+
+/// How the game ends when `flagged` runs out of time, it's only a draw if the other side has nothing but their king left
+pub fn timeout_end(board: &Board, flagged: TeamLoyalty) -> GameEnd {
+    let winner = flagged.opposite();
+    let bare_king = board.pieces().all(|(_, ent)| ent.team_id != winner || ent.entity_type == EntityType::KING);
+    if bare_king {
+        GameEnd::new(GameResult::Draw, Termination::Timeout)
+    } else {
+        GameEnd::loss_for(flagged, Termination::Timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn reads_time_controls() {
+        assert_eq!("300".parse(), Ok(TimeControl::sudden_death(secs(300))));
+        assert_eq!("300+5".parse(), Ok(TimeControl::fischer(secs(300), secs(5))));
+        assert_eq!("300d5".parse(), Ok(TimeControl::simple_delay(secs(300), secs(5))));
+        assert_eq!("300b2.5".parse(), Ok(TimeControl::bronstein(secs(300), Duration::from_millis(2500))));
+        assert_eq!("40/5400+30:1800+30".parse(), Ok(TimeControl::multi_period(vec![
+            Period { moves: Some(40), time: secs(5400), bonus: Bonus::Fischer(secs(30)) },
+            Period { moves: None, time: secs(1800), bonus: Bonus::Fischer(secs(30)) },
+        ])));
+    }
+
+    #[test]
+    fn rejects_bad_time_controls() {
+        for text in ["", "abc", "300+", "+5", "0/300", "x/300", "300+-1", "40/5400:", "300d5d5"] {
+            assert_eq!(text.parse::<TimeControl>(), Err(ParseTimeControlError(text.to_owned())), "{}", text);
+        }
+        assert!("300q".parse::<Period>().is_err());
+        assert!("1e30".parse::<TimeControl>().is_err());
+        assert!("300+inf".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn time_controls_round_trip() {
+        for text in ["300", "300+5", "180+0.5", "300d5", "300b2.5", "40/5400+30:1800+30", "40/7200:20/3600:900+30"] {
+            let control: TimeControl = text.parse().unwrap();
+            assert_eq!(control.to_string(), text);
+            assert_eq!(control.to_string().parse(), Ok(control));
+        }
+        let period = Period { moves: Some(20), time: Duration::from_millis(90500), bonus: Bonus::SimpleDelay(secs(10)) };
+        assert_eq!(period.to_string(), "20/90.5d10");
+        assert_eq!(period.to_string().parse(), Ok(period));
+    }
+
+    #[test]
+    fn fischer_adds_the_increment() {
+        let mut clock = ChessClock::new(TimeControl::fischer(secs(60), secs(2)));
+        clock.tick(TeamLoyalty::WHITE, secs(10));
+        clock.press(TeamLoyalty::WHITE);
+        assert_eq!(clock.remaining(TeamLoyalty::WHITE), secs(52));
+        assert_eq!(clock.remaining(TeamLoyalty::BLACK), secs(60));
+    }
+
+    #[test]
+    fn bronstein_gives_back_up_to_the_delay() {
+        let mut clock = ChessClock::new(TimeControl::bronstein(secs(60), secs(5)));
+        clock.tick(TeamLoyalty::WHITE, secs(3));
+        clock.press(TeamLoyalty::WHITE);
+        assert_eq!(clock.remaining(TeamLoyalty::WHITE), secs(60));
+
+        clock.tick(TeamLoyalty::BLACK, secs(8));
+        clock.press(TeamLoyalty::BLACK);
+        assert_eq!(clock.remaining(TeamLoyalty::BLACK), secs(57));
+    }
+
+    #[test]
+    fn simple_delay_waits_before_counting() {
+        let mut clock = ChessClock::new(TimeControl::simple_delay(secs(60), secs(5)));
+        clock.tick(TeamLoyalty::WHITE, secs(3));
+        assert_eq!(clock.remaining(TeamLoyalty::WHITE), secs(60));
+        clock.tick(TeamLoyalty::WHITE, secs(4));
+        assert_eq!(clock.remaining(TeamLoyalty::WHITE), secs(58));
+        clock.press(TeamLoyalty::WHITE);
+
+        // the delay starts over on the next move
+        clock.tick(TeamLoyalty::WHITE, secs(5));
+        assert_eq!(clock.remaining(TeamLoyalty::WHITE), secs(58));
+    }
+
+    #[test]
+    fn moves_on_to_the_next_period() {
+        let mut clock = ChessClock::new("2/60:30+1".parse().unwrap());
+        for _ in 0..2 {
+            clock.tick(TeamLoyalty::WHITE, secs(10));
+            clock.press(TeamLoyalty::WHITE);
+        }
+        assert_eq!(clock.remaining(TeamLoyalty::WHITE), secs(70));
+
+        // the second period has the increment
+        clock.tick(TeamLoyalty::WHITE, secs(10));
+        clock.press(TeamLoyalty::WHITE);
+        assert_eq!(clock.remaining(TeamLoyalty::WHITE), secs(61));
+    }
+
+    #[test]
+    fn repeats_the_last_period() {
+        let mut clock = ChessClock::new("1/10".parse().unwrap());
+        for _ in 0..3 {
+            clock.tick(TeamLoyalty::BLACK, secs(4));
+            clock.press(TeamLoyalty::BLACK);
+        }
+        assert_eq!(clock.remaining(TeamLoyalty::BLACK), secs(28));
+    }
+
+    #[test]
+    fn flags_when_time_runs_out() {
+        let mut clock = ChessClock::new(TimeControl::fischer(secs(5), secs(10)));
+        assert_eq!(clock.tick(TeamLoyalty::WHITE, secs(4)), None);
+        assert_eq!(clock.tick(TeamLoyalty::WHITE, secs(2)), Some(TeamLoyalty::WHITE));
+        assert_eq!(clock.flagged(), Some(TeamLoyalty::WHITE));
+        assert_eq!(clock.remaining(TeamLoyalty::WHITE), Duration::ZERO);
+
+        // a flag is final, no increment and no more ticking for the other side
+        clock.press(TeamLoyalty::WHITE);
+        assert_eq!(clock.remaining(TeamLoyalty::WHITE), Duration::ZERO);
+        assert_eq!(clock.tick(TeamLoyalty::BLACK, secs(1)), Some(TeamLoyalty::WHITE));
+        assert_eq!(clock.remaining(TeamLoyalty::BLACK), secs(5));
+    }
+}
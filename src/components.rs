@@ -1,5 +1,7 @@
 use crate::network_handler;
 use crate::chess_engine;
+use crate::clock;
+use crate::search;
 use bevy::prelude::*;
 use serde::Deserialize;
 use serde::Serialize;
@@ -12,6 +14,10 @@ pub const BLACK_TEXT: Color = Color::DARK_GRAY;
 pub const WHITE_TEXT: Color = Color::rgb(99., 103., 110.);
 pub const CELLSIZE: i32 = 64;
 pub const BUFFER_SIZE: i32 = 1024;
+pub const GAME_RECORD_DIR: &'static str = "games"; // finished matches get saved here as PGN
+pub const COMPUTER_THINK_TIME: std::time::Duration = std::time::Duration::from_millis(1500);
+pub const COMPUTER_NAME: &'static str = "Computer";
+pub const PRACTICE_TIME_CONTROL: &'static str = "600+5"; // 10 minutes with 5 seconds a move
 
 #[derive(Component)]
 pub struct GameScreenObject; // for cleanup post-match
@@ -28,9 +34,17 @@ pub struct CurrentPieceText;
 #[derive(Component)]
 pub struct CurrentFenText;
 
+#[derive(Component)]
+pub struct MoveErrorText; // why the last move was refused
+
 #[derive(Component)]
 pub struct CurrentMatchOpponent;
 
+#[derive(Component)]
+pub struct ClockText {
+    pub team: chess_engine::TeamLoyalty, // whose time this shows
+}
+
 #[derive(Component)]
 pub struct LobbyText;
 
@@ -40,13 +54,25 @@ pub struct LobbyPlayerCountText;
 #[derive(Component, Clone)]
 pub struct Piece;
 
+#[derive(Component, Clone)]
+pub struct PromotionChoice {
+    pub piece: chess_engine::EntityType,
+}
+
 #[derive(Component, Default, Clone, Debug)]
 pub struct GameState {
     pub board: chess_engine::Board,
     pub selected: Option<chess_engine::GameEntity>,
+    pub pending_promotion: Option<chess_engine::Move>, // move waiting on a promotion pick
     pub last_state: String,
     pub player_team: chess_engine::TeamLoyalty, // someone has to decide eventually
     pub original_cell_index: u32,
+    pub played_moves: Vec<chess_engine::Move>, // everything played so far, for the game record
+    pub move_error: Option<chess_engine::MoveError>, // why the player's last move didn't go through
+    pub vs_computer: bool, // practice game, nothing goes over the network
+    pub clock: Option<clock::ChessClock>, // None for untimed matches
+    pub match_data: MatchData,
+    pub opponent_name: String,
 }
 
 #[derive(Component, Default, Clone, Copy, Debug)]
@@ -65,6 +91,16 @@ pub struct StartButton;
 #[derive(Component)]
 pub struct QuitButton;
 
+#[derive(Component)]
+pub struct PracticeButton;
+
+/// Present while a practice game against the computer is being played
+#[derive(Resource)]
+pub struct ComputerOpponent {
+    pub limits: search::SearchLimits,
+    pub thinking: Option<std::thread::JoinHandle<Option<chess_engine::Move>>>, // search running in the background
+}
+
 /* For our assets */
 pub const PAWN_FILENAME: &'static str = "_pawn_png_shadow_128px.png";
 pub const ROOK_FILENAME: &'static str = "_rook_png_shadow_128px.png";
@@ -184,7 +220,7 @@ pub fn spawn_button(
         .id()
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
 pub struct MatchData {
     pub match_id: String,
     pub player_1: String,
@@ -192,6 +228,8 @@ pub struct MatchData {
     pub last_move: String,
     pub time_started: f32,
     pub time_ended: f32,
+    #[serde(default)]
+    pub time_control: String, // PGN style ("300+5"), empty for untimed matches
 }
 
 pub fn create_range_vector(x1: i32, x2: i32) -> Vec<i32> {
@@ -0,0 +1,404 @@
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use crate::bitboard::*;
+use crate::chess_engine::{Board, EntityType, TeamLoyalty};
+
+/// Where the evaluation weights get read from, edit it and restart to try new values
+pub const EVAL_WEIGHTS_FILE: &str = "assets/config/eval_weights.toml";
+
+// the shipped weights, used whenever the file above can't be read
+const DEFAULT_WEIGHTS: &str = include_str!("../assets/config/eval_weights.toml");
+
+// how much each piece (PIECE_TYPES order) counts towards the game phase, all of them on the board add up to MAX_PHASE
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+
+static WEIGHTS: Lazy<EvalWeights> = Lazy::new(|| {
+    let path = weights_path();
+    EvalWeights::load(&path.to_string_lossy()).unwrap_or_else(|err| {
+        eprintln!("evaluation::weights() -> {}, using the built in weights", err);
+        EvalWeights::default()
+    })
+});
+
+// the assets folder sits next to the executable once it's installed, while developing it's the one in the source tree.
+// not the working directory, GUIs and tournament managers start engines from anywhere
+fn weights_path() -> std::path::PathBuf {
+    let source_tree = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(EVAL_WEIGHTS_FILE);
+    let next_to_exe = std::env::current_exe().ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(EVAL_WEIGHTS_FILE)))
+        .filter(|path| path.is_file());
+    next_to_exe.unwrap_or(source_tree)
+}
+
+/// Why a weights file couldn't be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WeightsError {
+    Io(String),
+    Parse(String),
+    WrongLength { table: String, expected: usize, found: usize },
+}
+
+impl std::fmt::Display for WeightsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WeightsError::Io(err) => write!(f, "couldn't read the weights: {}", err),
+            WeightsError::Parse(err) => write!(f, "couldn't parse the weights: {}", err),
+            WeightsError::WrongLength { table, expected, found } =>
+                write!(f, "{} needs {} entries but has {}", table, expected, found),
+        }
+    }
+}
+
+impl std::error::Error for WeightsError {}
+
+/// A middlegame and an endgame value, blended by how much material is left
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct Taper {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Taper {
+    /// `phase` goes from MAX_PHASE (all pieces still on) down to 0 (bare kings and pawns)
+    pub fn blend(&self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl std::ops::Add for Taper {
+    type Output = Taper;
+
+    fn add(self, other: Taper) -> Taper {
+        Taper { mg: self.mg + other.mg, eg: self.eg + other.eg }
+    }
+}
+
+impl std::ops::Sub for Taper {
+    type Output = Taper;
+
+    fn sub(self, other: Taper) -> Taper {
+        Taper { mg: self.mg - other.mg, eg: self.eg - other.eg }
+    }
+}
+
+impl std::ops::AddAssign for Taper {
+    fn add_assign(&mut self, other: Taper) {
+        *self = *self + other;
+    }
+}
+
+impl std::ops::Mul<i32> for Taper {
+    type Output = Taper;
+
+    fn mul(self, times: i32) -> Taper {
+        Taper { mg: self.mg * times, eg: self.eg * times }
+    }
+}
+
+/// Everything that depends on the piece type
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PieceWeights {
+    pub value: Taper,
+    pub mobility: Taper, // per safe square the piece can go to
+    pub mg_table: Vec<i32>, // 64 squares from white's side, a8 first
+    pub eg_table: Vec<i32>,
+}
+
+impl PieceWeights {
+    // `square` is already seen from the piece's own side
+    fn on_square(&self, square: usize) -> Taper {
+        Taper { mg: self.mg_table[square], eg: self.eg_table[square] }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PawnStructureWeights {
+    pub doubled: Taper,
+    pub isolated: Taper,
+    pub passed_mg: Vec<i32>, // 2nd to 7th rank from the pawn's side
+    pub passed_eg: Vec<i32>,
+}
+
+impl PawnStructureWeights {
+    // `rank` 0 is the pawn's own back rank
+    fn passed(&self, rank: usize) -> Taper {
+        let index = rank.saturating_sub(1);
+        Taper {
+            mg: self.passed_mg.get(index).copied().unwrap_or(0),
+            eg: self.passed_eg.get(index).copied().unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct KingSafetyWeights {
+    pub pawn_shield: Taper,
+    pub open_file: Taper,
+    pub zone_attack: Taper,
+}
+
+/// Every number the evaluation uses, see assets/config/eval_weights.toml
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct EvalWeights {
+    pub pawn: PieceWeights,
+    pub knight: PieceWeights,
+    pub bishop: PieceWeights,
+    pub rook: PieceWeights,
+    pub queen: PieceWeights,
+    pub king: PieceWeights,
+    pub pawn_structure: PawnStructureWeights,
+    pub king_safety: KingSafetyWeights,
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        Self::from_toml(DEFAULT_WEIGHTS).expect("built in evaluation weights are broken")
+    }
+}
+
+impl EvalWeights {
+    pub fn load(path: &str) -> Result<Self, WeightsError> {
+        let text = std::fs::read_to_string(path).map_err(|err| WeightsError::Io(format!("{}: {}", path, err)))?;
+        Self::from_toml(&text)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, WeightsError> {
+        let weights: EvalWeights = toml::from_str(text).map_err(|err| WeightsError::Parse(err.to_string()))?;
+        weights.check()?;
+        Ok(weights)
+    }
+
+    pub fn piece(&self, entity_type: EntityType) -> &PieceWeights {
+        match entity_type {
+            EntityType::PAWN | EntityType::NOTSET => &self.pawn,
+            EntityType::KNIGHT => &self.knight,
+            EntityType::BISHOP => &self.bishop,
+            EntityType::ROOK => &self.rook,
+            EntityType::QUEEN => &self.queen,
+            EntityType::KING => &self.king,
+        }
+    }
+
+    // tables get indexed straight by square so their sizes have to be right
+    fn check(&self) -> Result<(), WeightsError> {
+        let expect = |table: String, expected: usize, found: usize| {
+            if found == expected { Ok(()) } else { Err(WeightsError::WrongLength { table, expected, found }) }
+        };
+        for entity_type in PIECE_TYPES {
+            let piece = self.piece(entity_type);
+            expect(format!("{:?}.mg_table", entity_type).to_lowercase(), 64, piece.mg_table.len())?;
+            expect(format!("{:?}.eg_table", entity_type).to_lowercase(), 64, piece.eg_table.len())?;
+        }
+        expect("pawn_structure.passed_mg".to_owned(), 6, self.pawn_structure.passed_mg.len())?;
+        expect("pawn_structure.passed_eg".to_owned(), 6, self.pawn_structure.passed_eg.len())
+    }
+}
+
+/// The weights `evaluate` uses, read from EVAL_WEIGHTS_FILE the first time they're needed
+pub fn weights() -> &'static EvalWeights {
+    &WEIGHTS
+}
+
+/// How good the position is for the side to move, in centipawns
+pub fn evaluate(board: &Board) -> i32 {
+    evaluate_with(board, weights())
+}
+
+/// Same as `evaluate` but with the given weights, handy for tuning
+pub fn evaluate_with(board: &Board, weights: &EvalWeights) -> i32 {
+    let bitboards = &board.bitboards;
+    let white = side_score(bitboards, TeamLoyalty::WHITE, weights);
+    let black = side_score(bitboards, TeamLoyalty::BLACK, weights);
+    let score = (white - black).blend(game_phase(bitboards));
+    if board.current_turn == TeamLoyalty::BLACK { -score } else { score }
+}
+
+/// MAX_PHASE with every piece on the board, down to 0 once only kings and pawns are left
+pub fn game_phase(bitboards: &Bitboards) -> i32 {
+    let mut phase = 0;
+    for (piece, entity_type) in PIECE_TYPES.iter().enumerate() {
+        let count = bitboards.piece(TeamLoyalty::WHITE, *entity_type).count_ones()
+            + bitboards.piece(TeamLoyalty::BLACK, *entity_type).count_ones();
+        phase += PHASE_WEIGHTS[piece] * count as i32;
+    }
+    // early promotions can push it past the start position
+    phase.min(MAX_PHASE)
+}
+
+fn file_mask(file: usize) -> Bitboard {
+    FILE_A << file
+}
+
+fn neighbour_files(file: usize) -> Bitboard {
+    let left = if file > 0 { file_mask(file - 1) } else { 0 };
+    let right = if file < 7 { file_mask(file + 1) } else { 0 };
+    left | right
+}
+
+// every row further up the board than `row` from `team`'s side (white moves towards row 0)
+fn rows_ahead(team: TeamLoyalty, row: usize) -> Bitboard {
+    if team == TeamLoyalty::WHITE {
+        (1 << (row * 8)) - 1
+    } else if row >= 7 {
+        0
+    } else {
+        !0 << ((row + 1) * 8)
+    }
+}
+
+// 0 is the team's own back rank
+fn relative_rank(team: TeamLoyalty, square: usize) -> usize {
+    if team == TeamLoyalty::WHITE { 7 - square / 8 } else { square / 8 }
+}
+
+// the tables are written from white's side, black reads them upside down
+fn relative_square(team: TeamLoyalty, square: usize) -> usize {
+    if team == TeamLoyalty::WHITE { square } else { square ^ 56 }
+}
+
+fn side_score(bitboards: &Bitboards, team: TeamLoyalty, weights: &EvalWeights) -> Taper {
+    let enemy = team.opposite();
+    let enemy_pawn_attacks = Squares(bitboards.piece(enemy, EntityType::PAWN))
+        .fold(0, |attacks, square| attacks | pawn_attacks(enemy, square));
+    // squares a piece could go to without getting chased off by a pawn straight away
+    let safe = !bitboards.team(team) & !enemy_pawn_attacks;
+
+    let mut score = Taper::default();
+    for entity_type in PIECE_TYPES {
+        let piece = weights.piece(entity_type);
+        for square in Squares(bitboards.piece(team, entity_type)) {
+            let reach = bitboards.piece_attacks(team, entity_type, square) & safe;
+            score += piece.value + piece.on_square(relative_square(team, square));
+            score += piece.mobility * reach.count_ones() as i32;
+        }
+    }
+    score + pawn_structure(bitboards, team, &weights.pawn_structure) + king_safety(bitboards, team, &weights.king_safety)
+}
+
+fn pawn_structure(bitboards: &Bitboards, team: TeamLoyalty, weights: &PawnStructureWeights) -> Taper {
+    let pawns = bitboards.piece(team, EntityType::PAWN);
+    let enemy_pawns = bitboards.piece(team.opposite(), EntityType::PAWN);
+    let mut score = Taper::default();
+
+    for file in 0..8 {
+        let on_file = (pawns & file_mask(file)).count_ones() as i32;
+        if on_file > 1 {
+            score += weights.doubled * (on_file - 1);
+        }
+    }
+
+    for square in Squares(pawns) {
+        let file = square % 8;
+        if pawns & neighbour_files(file) == 0 {
+            score += weights.isolated;
+        }
+        // nothing in front of it or on either side further up can stop it
+        let stoppers = (file_mask(file) | neighbour_files(file)) & rows_ahead(team, square / 8);
+        if enemy_pawns & stoppers == 0 {
+            score += weights.passed(relative_rank(team, square));
+        }
+    }
+    score
+}
+
+fn king_safety(bitboards: &Bitboards, team: TeamLoyalty, weights: &KingSafetyWeights) -> Taper {
+    let king = match bitboards.king_square(team) {
+        Some(king) => king,
+        None => return Taper::default(),
+    };
+    let pawns = bitboards.piece(team, EntityType::PAWN);
+    let mut score = Taper::default();
+
+    let shield = king_attacks(king) & rows_ahead(team, king / 8) & pawns;
+    score += weights.pawn_shield * shield.count_ones() as i32;
+
+    let file = king % 8;
+    for near_file in file.saturating_sub(1)..=(file + 1).min(7) {
+        if pawns & file_mask(near_file) == 0 {
+            score += weights.open_file;
+        }
+    }
+
+    let enemy = team.opposite();
+    let zone = king_attacks(king) | (1 << king);
+    let mut attacks = 0;
+    for entity_type in PIECE_TYPES {
+        for square in Squares(bitboards.piece(enemy, entity_type)) {
+            attacks += (bitboards.piece_attacks(enemy, entity_type, square) & zone).count_ones() as i32;
+        }
+    }
+    score + weights.zone_attack * attacks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_engine::DEFAULTFEN;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+
+    // the same position with the colours swapped, white's pieces become black's on the mirrored rank
+    fn mirror_fen(fen: &str) -> String {
+        let swap_case = |text: &str| -> String {
+            text.chars().map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect()
+        };
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let ranks: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let turn = if fields[1] == "w" { "b" } else { "w" };
+        let mut castling: Vec<char> = swap_case(fields[2]).chars().collect();
+        castling.sort();
+        let en_passant = match fields[3].as_bytes() {
+            [file, rank] => format!("{}{}", *file as char, (b'1' + b'8' - rank) as char),
+            _ => "-".to_owned(),
+        };
+        format!("{} {} {} {} {} {}", ranks.join("/"), turn, castling.iter().collect::<String>(), en_passant, fields[4], fields[5])
+    }
+
+    #[test]
+    fn colours_score_the_same() {
+        let weights = EvalWeights::default();
+        for fen in [DEFAULTFEN, KIWIPETE, POSITION_3, "rnbqkb1r/pp3ppp/4pn2/2pp4/3P4/2P1PN2/PP3PPP/RNBQKB1R w KQkq - 0 5"] {
+            let board = Board::from_fen(fen).unwrap();
+            let mirrored = Board::from_fen(&mirror_fen(fen)).unwrap();
+            assert_eq!(evaluate_with(&board, &weights), evaluate_with(&mirrored, &weights), "{}", fen);
+        }
+        // nothing to choose between the sides at the start
+        assert_eq!(evaluate_with(&Board::from_fen(DEFAULTFEN).unwrap(), &weights), 0);
+    }
+
+    #[test]
+    fn rejects_wrong_table_lengths() {
+        let short_passed = DEFAULT_WEIGHTS.replace("passed_mg = [0, 5, 10, 20, 35, 60]", "passed_mg = [0, 5, 10, 20, 35]");
+        assert_eq!(EvalWeights::from_toml(&short_passed), Err(WeightsError::WrongLength {
+            table: "pawn_structure.passed_mg".to_owned(),
+            expected: 6,
+            found: 5,
+        }));
+
+        let short_table = DEFAULT_WEIGHTS.replacen("mg_table = [\n      0,   0,   0,   0,   0,   0,   0,   0,\n", "mg_table = [\n", 1);
+        assert_eq!(EvalWeights::from_toml(&short_table), Err(WeightsError::WrongLength {
+            table: "pawn.mg_table".to_owned(),
+            expected: 64,
+            found: 56,
+        }));
+
+        assert!(matches!(EvalWeights::from_toml("[pawn]\nvalue = 3"), Err(WeightsError::Parse(_))));
+        assert!(EvalWeights::from_toml(DEFAULT_WEIGHTS).is_ok());
+    }
+
+    #[test]
+    fn game_phase_stays_in_bounds() {
+        let phase = |fen: &str| game_phase(&Board::from_fen(fen).unwrap().bitboards);
+        assert_eq!(phase(DEFAULTFEN), MAX_PHASE);
+        assert_eq!(phase("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), 0);
+        assert_eq!(phase("4k3/pppppppp/8/8/8/8/PPPPPPPP/4K3 w - - 0 1"), 0);
+        assert_eq!(phase("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"), 2);
+        // a board full of promoted queens doesn't go past the start
+        assert_eq!(phase("qqqqkqqq/qqqqqqqq/8/8/8/8/QQQQQQQQ/QQQQKQQQ w - - 0 1"), MAX_PHASE);
+    }
+}
@@ -5,15 +5,18 @@ use winapi::um::winuser;
 
 use bevy::prelude::*;
 
-use crate::chess_engine::TeamLoyalty;
+use crate::chess_engine::{GameEnd, TeamLoyalty, Termination};
+use crate::clock::{self, ChessClock, ClockSync, TimeControl};
 use crate::components::*;
 use crate::game_settings;
 use crate::chess_engine;
 use crate::game_settings::LogicalGameState;
 use crate::network_handler;
 use crate::network_handler::Client;
+use crate::pgn;
+use crate::search;
+use crate::square::Square;
 use bevy_interact_2d::*;
-use std::str::FromStr;
 use std::time::Duration;
 
 #[derive(Resource)]
@@ -30,10 +33,16 @@ impl Plugin for GameplayPlugin {
             .add_system_set(SystemSet::on_update(game_settings::LogicalGameState::Game)
                 .with_system(interaction_system)
                 .with_system(network_handle) // most important system
+                .with_system(resign_system)
+                .with_system(claim_draw_system)
+                .with_system(computer_opponent_system)
                 .with_system(draw_board) // this must come before update_board (otherwise causes fun race condition)
                 .with_system(update_board)
                 .with_system(update_fen_text)
                 .with_system(update_holding_text)
+                .with_system(update_move_error_text)
+                .with_system(clock_system)
+                .with_system(update_clock_text)
                 .with_system(update_turn_text)
             ).insert_resource(NetworkTimer {
                 timer: Timer::new(Duration::from_secs(2), TimerMode::Repeating)
@@ -48,86 +57,211 @@ fn network_handle(
     mut g_state: ResMut<State<LogicalGameState>>,
     time: Res<Time>
 ) {
-    
+    // practice games never talk to the server
+    if game_object.single().vs_computer { return; }
+
     net_timer.timer.tick(time.delta());
 
     // so we don't make a ton of requests
     if !net_timer.timer.finished() { return; }
 
+    // the server keeps the real clock, ours just fills in between polls
+    if game_object.single().clock.is_some() {
+        client.send("GCL".to_string()).unwrap(); // get the clock
+        let reply = client.recv().unwrap();
+        let times = reply.strip_prefix("CL").unwrap_or(&reply).trim_start_matches(SPLIT_CHAR);
+        if let (Some(sync), Some(clock)) = (ClockSync::from_message(times), game_object.single_mut().clock.as_mut()) {
+            clock.sync(&sync);
+        }
+    }
+
     client.send("GLM".to_string()).unwrap(); // get the last move
     let result = client.recv().unwrap();
 
     // ignore these messages from the server (CAN AND WILL Cause issues)
     if result == "START_OF_MATCH" || result == "UPDATED" { return; } // nothing to do here (no moves yet)
 
-    // time to parse 😔
-    let data_points: Vec<&str> = result.split(":").collect();
+    let mut game_state = game_object.single_mut();
 
-    let (x1, y1) = {
-        let x = match FromStr::from_str(data_points[0]) {
-            Ok(n) => n,
-            Err(_) => -1,
-        };
+    // once the match is closed the server hands back the end message instead of a move
+    if let Some(data) = result.strip_prefix("EM") {
+        if let Some(end) = GameEnd::from_message(data.trim_start_matches(SPLIT_CHAR)) {
+            finish_game(&game_state, &client.player_name, end, &mut g_state);
+        }
+        return;
+    }
 
-        let y = match FromStr::from_str(data_points[1]) {
-            Ok(n) => n,
-            Err(_) => -1,
-        };
+    // moves come across in UCI long algebraic form ("e2e4", "e7e8q")
+    let mv = match chess_engine::Move::from_uci(&result) {
+        Some(mv) => mv,
+        None => return, // bad input
+    };
 
-        (x, y)
+    // GLM keeps handing back the last move, so anything already played comes back as NoPiece
+    if let Err(err) = play_and_record(&mut game_state, mv, false) {
+        if err != chess_engine::MoveError::NoPiece {
+            println!("game_screen::network_handle() -> Rejected {}: {}", result, err);
+        }
+        return;
+    }
+
+    // the opponent made the last move, so they're the one closing the match (see send_move)
+    if let Some(end) = game_state.board.outcome().forced_end() {
+        finish_game(&game_state, &client.player_name, end, &mut g_state);
+    }
+}
+
+// runs the clock of whoever is to move, running out of time loses (or draws against a bare king)
+fn clock_system(
+    mut game_object: Query<&mut GameState, With<GlobalThing>>,
+    mut client: ResMut<Client>,
+    mut g_state: ResMut<State<LogicalGameState>>,
+    time: Res<Time>
+) {
+    let mut game_state = game_object.single_mut();
+    if game_state.board.outcome().is_terminal() { return; }
+
+    let turn = game_state.board.current_turn;
+    let flagged = match game_state.clock.as_mut().and_then(|clock| clock.tick(turn, time.delta())) {
+        Some(flagged) => flagged,
+        None => return,
     };
 
-    let (x2, y2) = {
-        let x = match FromStr::from_str(data_points[0]) {
-            Ok(n) => n,
-            Err(_) => -1,
-        };
+    // the opponent reports their own flag, it comes back from the server as an EM through GLM
+    if flagged != game_state.player_team && !game_state.vs_computer { return; }
 
-        let y = match FromStr::from_str(data_points[1]) {
-            Ok(n) => n,
-            Err(_) => -1,
-        };
+    let end = clock::timeout_end(&game_state.board, flagged);
+    if !game_state.vs_computer {
+        client.send_cmd("EM".to_string(), end.to_message()).unwrap();
+    }
+    finish_game(&game_state, &client.player_name, end, &mut g_state);
+}
 
-        (x, y)
+fn termination_text(termination: Termination) -> &'static str {
+    match termination {
+        Termination::Checkmate => "by checkmate",
+        Termination::Resignation => "by resignation",
+        Termination::Timeout => "on time",
+        Termination::Stalemate => "by stalemate",
+        Termination::Repetition => "by threefold repetition",
+        Termination::FiftyMoveRule => "by the fifty move rule",
+        Termination::InsufficientMaterial => "as neither side can checkmate",
+        Termination::Agreement => "by agreement",
+        Termination::Abandonment => "as the game was abandoned",
+    }
+}
+
+// saves the game, tells the player how it went and heads back to the menu
+fn finish_game(game_state: &GameState, player_name: &str, end: GameEnd, g_state: &mut State<LogicalGameState>) {
+    save_game_record(game_state, player_name, &end);
+
+    let verdict = match end.result.winner() {
+        Some(winner) if winner == game_state.player_team => "You have won",
+        Some(_) => "You have lost",
+        None => "The game is a draw",
     };
+    let msg = format!("{} {}!", verdict, termination_text(end.termination));
+    println!("game_screen::finish_game() -> {:?}", end);
 
-    if x1 < 0 || x2 < 0 || y1 < 0 || y2 < 0 {
-        //bad input
-        return;
+    let l_title: Vec<u16> = "\u{265A} Bitmato Chess".encode_utf16().chain(Some(0)).collect();
+    let l_msg: Vec<u16> = msg.encode_utf16().chain(Some(0)).collect();
+    unsafe {
+        winuser::MessageBoxW(NULL(), l_msg.as_ptr(), l_title.as_ptr(), winuser::MB_OK | winuser::MB_ICONINFORMATION);
     }
+    g_state.set(LogicalGameState::Menu).unwrap();
+}
 
-    let origin = chess_engine::Vec2 {
-        x: x1,
-        y: y1,
+// escape offers to resign the game
+fn resign_system(
+    keys: Res<Input<KeyCode>>,
+    game_object: Query<&GameState, With<GlobalThing>>,
+    mut client: ResMut<Client>,
+    mut g_state: ResMut<State<LogicalGameState>>
+) {
+    if !keys.just_pressed(KeyCode::Escape) { return; }
+
+    let game_state = game_object.single();
+    if game_state.board.outcome().is_terminal() { return; }
+
+    let l_title: Vec<u16> = "\u{265A} Bitmato Chess".encode_utf16().chain(Some(0)).collect();
+    let l_msg: Vec<u16> = "Resign this game?".encode_utf16().chain(Some(0)).collect();
+    let answer = unsafe {
+        winuser::MessageBoxW(NULL(), l_msg.as_ptr(), l_title.as_ptr(), winuser::MB_YESNO | winuser::MB_ICONQUESTION)
     };
+    if answer != winuser::IDYES { return; }
 
-    let dest = chess_engine::Vec2 {
-        x: x2,
-        y: y2,
+    let end = GameEnd::loss_for(game_state.player_team, Termination::Resignation);
+    if !game_state.vs_computer {
+        client.send_cmd("EM".to_string(), end.to_message()).unwrap();
+    }
+    finish_game(game_state, &client.player_name, end, &mut g_state);
+}
+
+// D claims a draw once the position has repeated three times or fifty moves went by without a capture or pawn move
+fn claim_draw_system(
+    keys: Res<Input<KeyCode>>,
+    game_object: Query<&GameState, With<GlobalThing>>,
+    mut client: ResMut<Client>,
+    mut g_state: ResMut<State<LogicalGameState>>
+) {
+    if !keys.just_pressed(KeyCode::D) { return; }
+
+    let game_state = game_object.single();
+    let outcome = game_state.board.outcome();
+    let end = match outcome.game_end() {
+        Some(end) if outcome.is_claimable() => end,
+        _ => return,
     };
 
-    let mut game_state = game_object.single_mut();
+    let l_title: Vec<u16> = "\u{265A} Bitmato Chess".encode_utf16().chain(Some(0)).collect();
+    let l_msg: Vec<u16> = format!("Claim a draw {}?", termination_text(end.termination)).encode_utf16().chain(Some(0)).collect();
+    let answer = unsafe {
+        winuser::MessageBoxW(NULL(), l_msg.as_ptr(), l_title.as_ptr(), winuser::MB_YESNO | winuser::MB_ICONQUESTION)
+    };
+    if answer != winuser::IDYES { return; }
 
-    chess_engine::move_entity(&mut game_state.board, origin, dest, true);
+    // whoever claims closes the match, the opponent gets the EM through GLM
+    if !game_state.vs_computer {
+        client.send_cmd("EM".to_string(), end.to_message()).unwrap();
+    }
+    finish_game(game_state, &client.player_name, end, &mut g_state);
+}
 
-    let l_title: Vec<u16> = "\u{265A} Bitmato Chess".encode_utf16().collect();
-    if game_state.board.is_checkmate && game_state.player_team != game_state.board.current_turn {
-        // this player lost
-        let l_msg: Vec<u16> = "You have lost!".encode_utf16().collect();
-        unsafe {
-            winuser::MessageBoxW(NULL(), l_msg.as_ptr(), l_title.as_ptr(), winuser::MB_OK | winuser::MB_ICONINFORMATION);
-        }
-        g_state.set(LogicalGameState::Menu).unwrap();
-    } else if game_state.board.is_checkmate && game_state.player_team == game_state.board.current_turn {
-        // this player won
-        let l_msg: Vec<u16> = "You have won!".encode_utf16().collect();
-        client.send("EM".to_string()).unwrap(); // end the match
-        unsafe {
-            winuser::MessageBoxW(NULL(), l_msg.as_ptr(), l_title.as_ptr(), winuser::MB_OK | winuser::MB_ICONINFORMATION);
-        }
-        g_state.set(LogicalGameState::Menu).unwrap();
+// plays the other side of a practice game, the search runs on its own thread so the board keeps drawing
+fn computer_opponent_system(
+    mut game_object: Query<&mut GameState, With<GlobalThing>>,
+    computer: Option<ResMut<ComputerOpponent>>,
+    client: Res<Client>,
+    mut g_state: ResMut<State<LogicalGameState>>
+) {
+    let mut computer = match computer {
+        Some(computer) => computer,
+        None => return,
+    };
+    let mut game_state = game_object.single_mut();
+
+    if let Some(end) = game_state.board.outcome().forced_end() {
+        computer.thinking = None;
+        finish_game(&game_state, &client.player_name, end, &mut g_state);
+        return;
     }
+    if game_state.board.current_turn == game_state.player_team { return; }
 
+    match computer.thinking.take() {
+        None => {
+            let board = game_state.board.clone();
+            let limits = computer.limits.clone();
+            computer.thinking = Some(std::thread::spawn(move || search::best_move(&board, limits)));
+        },
+        Some(thinking) if thinking.is_finished() => {
+            if let Ok(Some(mv)) = thinking.join() {
+                if let Err(err) = play_and_record(&mut game_state, mv, false) {
+                    println!("game_screen::computer_opponent_system() -> Computer tried {}: {}", mv, err);
+                }
+            }
+        },
+        Some(thinking) => computer.thinking = Some(thinking),
+    }
 }
 
 fn update_board(
@@ -201,12 +335,120 @@ fn ecs_fix(index: u32) -> u32 {
     }
 }
 
+// plays the move and keeps it for the game record if it actually went through
+fn play_and_record(game_state: &mut GameState, mv: chess_engine::Move, ignore_checks: bool) -> Result<chess_engine::MoveOutcome, chess_engine::MoveError> {
+    // move_entity also changes current team
+    let mover = game_state.board.current_turn;
+    let outcome = chess_engine::move_entity(&mut game_state.board, mv, ignore_checks)?;
+    game_state.played_moves.push(outcome.mv);
+    if let Some(clock) = game_state.clock.as_mut() {
+        clock.press(mover);
+    }
+    Ok(outcome)
+}
+
+// writes the finished match out as PGN so it can be reviewed later
+fn save_game_record(game_state: &GameState, player_name: &str, end: &GameEnd) {
+    let start = chess_engine::Board::create_board(chess_engine::DEFAULTFEN.into());
+    let mut game = match pgn::PgnGame::from_moves(&start, &game_state.played_moves) {
+        Ok(game) => game,
+        Err(err) => {
+            println!("game_screen::save_game_record() -> Not saving the game: {}", err);
+            return;
+        },
+    };
+    game.set_match_data(&game_state.match_data);
+
+    let (white, black) = if game_state.player_team == TeamLoyalty::WHITE {
+        (player_name, game_state.opponent_name.as_str())
+    } else {
+        (game_state.opponent_name.as_str(), player_name)
+    };
+    game.set_tag("White", white);
+    game.set_tag("Black", black);
+    game.set_game_end(end);
+
+    let path = format!("{}/{}.pgn", GAME_RECORD_DIR, game_state.match_data.match_id);
+    let text = match game.to_pgn() {
+        Ok(text) => text,
+        Err(err) => {
+            println!("game_screen::save_game_record() -> Not saving the game: {}", err);
+            return;
+        },
+    };
+    let saved = std::fs::create_dir_all(GAME_RECORD_DIR).and_then(|_| std::fs::write(&path, text));
+    match saved {
+        Ok(_) => println!("game_screen::save_game_record() -> Saved {}", path),
+        Err(err) => println!("game_screen::save_game_record() -> Failed to save {}: {}", path, err),
+    }
+}
+
+// plays the move locally and lets the server (and so the opponent) know about it
+fn send_move(game_state: &mut GameState, client: &mut network_handler::Client, g_state: &mut State<LogicalGameState>, mv: chess_engine::Move) {
+    game_state.selected = None;
+
+    // illegal moves stay here, the player just gets told why
+    let played = play_and_record(game_state, mv, false);
+    game_state.move_error = played.err();
+    if played.is_err() || game_state.vs_computer { return; }
+
+    // we need to also send 
+    // the UP command along with UF
+    // TODO: Send UP & UF in one cmd?
+
+    client.send_cmd("UF".to_string(), game_state.board.to_fen()).unwrap();
+
+    // this is the most important part (UF is really secondary in this)
+    client.send_cmd("UP".to_string(), mv.to_uci()).unwrap();
+
+    // the player who made the last move closes the match, GLM only ever echoes this move back to us
+    if let Some(end) = game_state.board.outcome().forced_end() {
+        client.send_cmd("EM".to_string(), end.to_message()).unwrap();
+        finish_game(game_state, &client.player_name, end, g_state);
+    }
+}
+
+// the four pieces a pawn can become, shown to the right of the board
+fn spawn_promotion_picker(commands: &mut Commands, game_assets: &AssetHandler, team: TeamLoyalty) {
+    let x_pos = CELLSIZE * 2;
+    let y_pos = CELLSIZE;
+
+    for (i, piece) in chess_engine::PROMOTION_CHOICES.iter().enumerate() {
+        commands.spawn(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(CELLSIZE as f32, CELLSIZE as f32)),
+                ..default()
+            },
+            texture: get_piece_asset(game_assets, piece, team),
+            transform: Transform {
+                translation: Vec3 {
+                    x: (x_pos + CELLSIZE * i as i32) as f32,
+                    y: y_pos as f32,
+                    z: 1.,
+                },
+                ..default()
+            },
+            ..default()
+        }).insert(PromotionChoice { piece: *piece })
+        .insert(Interactable {
+            groups: vec![Group(1)],
+            bounding_box: (Vec2::new(-(CELLSIZE/2) as f32, -(CELLSIZE/2) as f32), Vec2::new((CELLSIZE/2) as f32, (CELLSIZE/2) as f32)),
+            ..default()
+        })
+        .insert(GameScreenObject);
+    }
+}
+
 fn interaction_system(
+    mut commands: Commands,
     mouse_button_input: Res<Input<MouseButton>>,
     interaction_state: Res<InteractionState>,
     cells_structs: Query<&mut Cell, With<Cell>>,
+    promotion_choices: Query<(Entity, &PromotionChoice)>,
     mut global_structs: Query<&mut GameState, With<GlobalThing>>,
-    mut client: ResMut<network_handler::Client>
+    mut client: ResMut<network_handler::Client>,
+    mut g_state: ResMut<State<LogicalGameState>>,
+    game_assets: Res<AssetHandler>
 ) {
 
     let mut game_state = global_structs.single_mut();
@@ -215,6 +457,22 @@ fn interaction_system(
         return;
     }
 
+    // waiting on the player to pick what their pawn becomes
+    if let Some(pending) = game_state.pending_promotion {
+        for (ent, _) in interaction_state.get_group(Group(1)).iter() {
+            if let Ok((_, choice)) = promotion_choices.get(*ent) {
+                let mv = chess_engine::Move::with_promotion(pending.from, pending.to, choice.piece);
+                game_state.pending_promotion = None;
+                for (picker_ent, _) in promotion_choices.iter() {
+                    commands.entity(picker_ent).despawn();
+                }
+                send_move(&mut game_state, &mut client, &mut g_state, mv);
+                return;
+            }
+        }
+        return;
+    }
+
     let mut index = 0;
 
     // no new fen yet
@@ -236,19 +494,14 @@ fn interaction_system(
             let origin = cells[cell_original as usize].position;
             let destination = cells[index as usize].position;
 
-            // move_entity also changes current team
-            chess_engine::move_entity(&mut game_state.board, origin, destination, false);
-            game_state.selected = None;
-
-            // we need to also send 
-            // the UP command along with UF
-            // TODO: Send UP & UF in one cmd?
-
-            client.send_cmd("UF".to_string(), game_state.board.to_fen()).unwrap();
+            if chess_engine::is_promotion_move(&game_state.board, origin, destination) {
+                // hold the move until a piece is picked
+                game_state.pending_promotion = Some(chess_engine::Move::new(origin, destination));
+                spawn_promotion_picker(&mut commands, &game_assets, game_state.player_team);
+                return;
+            }
 
-            // this is the most important part (UF is really secondary in this)
-            let msg = format!("{}:{}:{}:{}", origin.x, origin.y, destination.x, destination.y);
-            client.send_cmd("UP".to_string(), msg).unwrap();
+            send_move(&mut game_state, &mut client, &mut g_state, chess_engine::Move::new(origin, destination));
             
             return;
         } else {
@@ -302,6 +555,72 @@ fn update_holding_text (
     };
 }
 
+fn update_move_error_text(
+    global_thing: Query<&GameState, With<GlobalThing>>,
+    mut move_error_query: Query<&mut Text, With<MoveErrorText>>
+) {
+    let game_state = global_thing.single();
+    let mut move_error_text = move_error_query.single_mut();
+    let outcome = game_state.board.outcome();
+    move_error_text.sections[0].value = match (game_state.move_error, outcome.game_end()) {
+        (Some(err), _) => format!("Can't move there, {}", err),
+        (None, Some(end)) if outcome.is_claimable() => format!("Press D to claim a draw {}", termination_text(end.termination)),
+        _ => String::new(),
+    };
+}
+
+fn update_clock_text(
+    global_thing: Query<&GameState, With<GlobalThing>>,
+    mut clock_query: Query<(&mut Text, &ClockText)>
+) {
+    let game_state = global_thing.single();
+    let clock = match &game_state.clock {
+        Some(clock) => clock,
+        None => return,
+    };
+    for (mut clock_text, shows) in clock_query.iter_mut() {
+        clock_text.sections[1].value = clock::format_clock(clock.remaining(shows.team));
+        clock_text.sections[1].style.color = if clock.flagged() == Some(shows.team) {
+            Color::ORANGE_RED
+        } else if game_state.board.current_turn == shows.team {
+            Color::GOLD
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
+fn spawn_clock_text(commands: &mut Commands, game_assets: &AssetHandler, label: &str, team: TeamLoyalty, bottom: f32) {
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(
+                label,
+                TextStyle {
+                    font: game_assets.global_font.clone(),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            ),
+            TextSection::from_style(TextStyle {
+                font: game_assets.global_font.clone(),
+                font_size: 30.0,
+                color: Color::WHITE,
+            }),
+        ]).with_text_alignment(TextAlignment::BOTTOM_LEFT)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(bottom),
+                left: Val::Px(600.0),
+                ..default()
+            },
+            ..default()
+        }),
+        ClockText { team },
+        GameScreenObject
+    ));
+}
+
 fn get_player_color(m_data: &MatchData, net_cli: &network_handler::Client) -> chess_engine::TeamLoyalty {
     if net_cli.user_id == m_data.player_1 {
         chess_engine::TeamLoyalty::WHITE
@@ -310,32 +629,75 @@ fn get_player_color(m_data: &MatchData, net_cli: &network_handler::Client) -> ch
     }
 }
 
+// a practice game has no server match behind it, the player is always white
+fn practice_match_data(client: &network_handler::Client) -> (MatchData, TeamLoyalty, String) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0);
+    let data = MatchData {
+        match_id: format!("practice_{}", now),
+        player_1: client.user_id.clone(),
+        player_2: COMPUTER_NAME.to_owned(),
+        time_started: now as f32,
+        time_control: PRACTICE_TIME_CONTROL.to_owned(),
+        ..default()
+    };
+    (data, TeamLoyalty::WHITE, COMPUTER_NAME.to_owned())
+}
+
 fn setup(
     mut commands: Commands, 
     asset_server: Res<AssetServer>, 
     mut meshes: ResMut<Assets<Mesh>>, 
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut client: ResMut<network_handler::Client>,
+    computer: Option<Res<ComputerOpponent>>,
     game_assets: Res<AssetHandler>
 ) {
-    client.recv().unwrap();
-    client.send("GMD".to_string()).unwrap();
-    let match_str_data = loop {
-        let data = client.recv().unwrap();
-        if data.chars().next().unwrap() != '{' {
-            continue;
-        }
-        break data;
+    let (data, player_color, op_name) = if computer.is_some() {
+        practice_match_data(&client)
+    } else {
+        client.recv().unwrap();
+        client.send("GMD".to_string()).unwrap();
+        let match_str_data = loop {
+            let data = client.recv().unwrap();
+            if data.chars().next().unwrap() != '{' {
+                continue;
+            }
+            break data;
+        };
+        let data: MatchData = serde_json::from_str(&match_str_data.trim()).unwrap();
+        let player_color = get_player_color(&data, &client);
+
+        client.send_cmd("GPN".to_string(), if player_color == TeamLoyalty::WHITE { data.player_2.clone() } else { data.player_1.clone() }).unwrap();
+        let op_name = client.recv().unwrap();
+        (data, player_color, op_name)
+    };
+
+    // no (or an unreadable) time control means an untimed match
+    let clock = match data.time_control.parse::<TimeControl>() {
+        Ok(control) => Some(ChessClock::new(control)),
+        Err(err) => {
+            if !data.time_control.is_empty() { println!("game_screen::setup() -> {}, playing untimed", err); }
+            None
+        },
     };
-    let data: MatchData = serde_json::from_str(&match_str_data.trim()).unwrap();
-    let player_color = get_player_color(&data, &client);
+    let timed = clock.is_some();
 
     let game_state = GameState {
         board: chess_engine::Board::create_board(chess_engine::DEFAULTFEN.into()),
         selected: None,
+        pending_promotion: None,
         last_state: String::new(),
         player_team: player_color,
         original_cell_index: 0,
+        played_moves: Vec::new(),
+        move_error: None,
+        vs_computer: computer.is_some(),
+        clock,
+        match_data: data,
+        opponent_name: op_name.clone(),
     };
 
     let color1 = Color::hex("9da19d").unwrap();
@@ -376,10 +738,11 @@ fn setup(
             })
             .insert(GameScreenObject).id();
             color_index += 1;
-            let obj = game_state.board.entity_at(chess_engine::Vec2 { x: col, y: row }).clone();
+            let square = Square::from_position(chess_engine::Vec2 { x: col, y: row }).unwrap();
+            let obj = game_state.board.piece_at(square);
 
             if obj.is_some() {
-                println!("game_screen::setup() -> {} => {:?}", id.index(), obj.unwrap());
+                println!("game_screen::setup() -> {} ({}) => {:?}", id.index(), square, obj.unwrap());
             }
         }
     }
@@ -452,8 +815,27 @@ fn setup(
         GameScreenObject
     ));
 
-    client.send_cmd("GPN".to_string(), if player_color == TeamLoyalty::WHITE { data.player_2 } else { data.player_1 }).unwrap();
-    let op_name = client.recv().unwrap();
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: game_assets.global_font.clone(),
+                font_size: 20.0,
+                color: Color::ORANGE_RED,
+            },
+        ).with_text_alignment(TextAlignment::BOTTOM_LEFT)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(140.0),
+                left: Val::Px(15.0),
+                ..default()
+            },
+            ..default()
+        }),
+        MoveErrorText,
+        GameScreenObject
+    ));
 
 
     commands.spawn((
@@ -480,6 +862,11 @@ fn setup(
         GameScreenObject
     ));
 
+    if timed {
+        spawn_clock_text(&mut commands, &game_assets, "Opponent Clock: ", player_color.opposite(), 460.0);
+        spawn_clock_text(&mut commands, &game_assets, "Your Clock: ", player_color, 420.0);
+    }
+
     commands.spawn((
         TextBundle::from_sections([
             TextSection::new( 
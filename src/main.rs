@@ -19,6 +19,13 @@ mod network_handler;
 // mod splash_screen;
 mod game_settings;
 mod chess_engine;
+mod bitboard;
+mod square;
+mod evaluation;
+mod search;
+mod clock;
+mod notation;
+mod pgn;
 mod game_screen;
 mod lobby_setup;
 mod components;
@@ -2,6 +2,7 @@
 use bevy::{app::AppExit, prelude::*};
 use crate::components::*;
 use crate::game_settings;
+use crate::search;
 
 pub struct MainMenuPlugin;
 
@@ -12,6 +13,7 @@ impl Plugin for MainMenuPlugin {
             .add_system_set(
                 SystemSet::on_update(game_settings::LogicalGameState::Menu)
                     .with_system(start_button_clicked)
+                    .with_system(practice_button_clicked)
                     .with_system(quit_button_clicked),
             );
     }
@@ -27,6 +29,7 @@ fn start_button_clicked(
         if matches!(interaction, Interaction::Clicked) {
             let root_entity = menu_root.single();
             commands.entity(root_entity).despawn_recursive();
+            commands.remove_resource::<ComputerOpponent>();
 
             // Change Me! LogicalGameState::Game to LogicalGameState::Lobby - May
             game_state.set(game_settings::LogicalGameState::Lobby).unwrap();
@@ -34,6 +37,27 @@ fn start_button_clicked(
     }
 }
 
+// straight into a game against the computer, no lobby or server needed
+fn practice_button_clicked(
+    mut commands: Commands,
+    interactions: Query<&Interaction, (With<PracticeButton>, Changed<Interaction>)>,
+    menu_root: Query<Entity, With<MenuUIroot>>,
+    mut game_state: ResMut<State<game_settings::LogicalGameState>>
+) {
+    for interaction in &interactions {
+        if matches!(interaction, Interaction::Clicked) {
+            let root_entity = menu_root.single();
+            commands.entity(root_entity).despawn_recursive();
+            commands.insert_resource(ComputerOpponent {
+                limits: search::SearchLimits::time(COMPUTER_THINK_TIME),
+                thinking: None,
+            });
+
+            game_state.set(game_settings::LogicalGameState::Game).unwrap();
+        }
+    }
+}
+
 fn quit_button_clicked(
     interactions: Query<&Interaction, (With<QuitButton>, Changed<Interaction>)>,
     mut exit: EventWriter<AppExit>,
@@ -58,6 +82,9 @@ fn spawn_main_menu(
     let start_button = spawn_button(&mut commands, &asset_server, "Start Game", Color::RED);
     commands.entity(start_button).insert(StartButton);
 
+    let practice_button = spawn_button(&mut commands, &asset_server, "Practice", Color::GREEN);
+    commands.entity(practice_button).insert(PracticeButton);
+
     let quit_button = spawn_button(&mut commands, &asset_server, "Quit", Color::BLUE);
     commands.entity(quit_button).insert(QuitButton);
 
@@ -90,5 +117,6 @@ fn spawn_main_menu(
             });
         })
         .add_child(start_button)
+        .add_child(practice_button)
         .add_child(quit_button);
 }
@@ -0,0 +1,263 @@
+use crate::chess_engine::*;
+
+/// Why a SAN string couldn't be turned into a move on the given board
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanError {
+    Empty,
+    InvalidSyntax(String),
+    NoMatchingMove(String),
+    Ambiguous(String),
+}
+
+impl std::fmt::Display for SanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SanError::Empty => write!(f, "move is empty"),
+            SanError::InvalidSyntax(san) => write!(f, "'{}' is not valid SAN", san),
+            SanError::NoMatchingMove(san) => write!(f, "'{}' is not a legal move here", san),
+            SanError::Ambiguous(san) => write!(f, "'{}' could mean more than one move", san),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+fn uci_promotion(c: char) -> Option<EntityType> {
+    match c {
+        'q' | 'r' | 'b' | 'n' => Some(get_entity_type(c)),
+        _ => None,
+    }
+}
+
+fn san_piece_letter(entity_type: EntityType) -> String {
+    get_entity_fen(&entity_type).to_ascii_uppercase().to_string()
+}
+
+impl Move {
+    /// Standard Algebraic Notation for this move, `board` is the position before it is played
+    pub fn to_san(self, board: &Board) -> String {
+        let piece = match board.entity_at(self.from) {
+            Some(ent) => *ent,
+            None => return String::new(),
+        };
+        let mv = create_move(board, self.from, self.to, self.promotion);
+
+        let mut out = if mv.flags.castle {
+            if self.to.x > self.from.x { "O-O".to_owned() } else { "O-O-O".to_owned() }
+        } else {
+            let is_capture = mv.flags.capture;
+            let mut san = String::new();
+
+            if piece.entity_type == EntityType::PAWN {
+                if is_capture {
+                    san.push((b'a' + self.from.x as u8) as char);
+                }
+            } else {
+                san += &san_piece_letter(piece.entity_type);
+
+                // other pieces of the same kind that could also land here
+                let rivals: Vec<Move> = legal_moves(board).into_iter()
+                    .filter(|mv| mv.to == self.to && mv.from != self.from)
+                    .filter(|mv| board.entity_at(mv.from).map(|ent| ent.entity_type) == Some(piece.entity_type))
+                    .collect();
+                if !rivals.is_empty() {
+                    let from_name = position_to_algebraic(self.from);
+                    if rivals.iter().all(|mv| mv.from.x != self.from.x) {
+                        san += &from_name[0..1];
+                    } else if rivals.iter().all(|mv| mv.from.y != self.from.y) {
+                        san += &from_name[1..2];
+                    } else {
+                        san += &from_name;
+                    }
+                }
+            }
+
+            if is_capture {
+                san.push('x');
+            }
+            san += &position_to_algebraic(self.to);
+
+            if let Some(promotion) = self.promotion {
+                san += "=";
+                san += &san_piece_letter(promotion);
+            }
+            san
+        };
+
+        let mut after = board.clone();
+        after.make_move(mv);
+        if after.is_checkmate {
+            out.push('#');
+        } else if after.is_check {
+            out.push('+');
+        }
+        out
+    }
+
+    /// Finds the legal move on `board` described by `san`, check/mate and annotation marks are optional
+    pub fn from_san(board: &Board, san: &str) -> Result<Move, SanError> {
+        let trimmed = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        if trimmed.is_empty() {
+            return Err(SanError::Empty);
+        }
+
+        let moves = legal_moves(board);
+
+        if matches!(trimmed, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+            let king_side = trimmed.len() == 3;
+            return moves.into_iter()
+                .find(|mv| mv.flags.castle && (mv.to.x > mv.from.x) == king_side)
+                .ok_or_else(|| SanError::NoMatchingMove(san.to_owned()));
+        }
+
+        let invalid = || SanError::InvalidSyntax(san.to_owned());
+        let mut chars: Vec<char> = trimmed.chars().collect();
+
+        // promotion on the end, "e8=Q" or the older "e8Q"
+        let mut promotion: Option<EntityType> = None;
+        if let Some(last) = chars.last() {
+            if "QRBN".contains(*last) && chars.len() > 2 {
+                promotion = Some(get_entity_type(*last));
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+            }
+        }
+
+        if chars.len() < 2 {
+            return Err(invalid());
+        }
+        let dest: String = chars.split_off(chars.len() - 2).into_iter().collect();
+        let to = algebraic_to_position(&dest).ok_or_else(invalid)?;
+
+        let mut entity_type = EntityType::PAWN;
+        let mut prefix = chars.as_slice();
+        if let Some(first) = prefix.first() {
+            if "KQRBN".contains(*first) {
+                entity_type = get_entity_type(*first);
+                prefix = &prefix[1..];
+            }
+        }
+
+        let (mut from_x, mut from_y): (Option<i32>, Option<i32>) = (None, None);
+        for c in prefix {
+            match c {
+                'a'..='h' => from_x = Some(*c as i32 - 'a' as i32),
+                '1'..='8' => from_y = Some('8' as i32 - *c as i32),
+                'x' => {},
+                _ => return Err(invalid()),
+            }
+        }
+
+        let matching: Vec<Move> = moves.into_iter()
+            .filter(|mv| mv.to == to && mv.promotion == promotion)
+            .filter(|mv| board.entity_at(mv.from).map(|ent| ent.entity_type) == Some(entity_type))
+            .filter(|mv| (from_x.is_none() || from_x == Some(mv.from.x)) && (from_y.is_none() || from_y == Some(mv.from.y)))
+            .collect();
+
+        match matching.len() {
+            0 => Err(SanError::NoMatchingMove(san.to_owned())),
+            1 => Ok(matching[0]),
+            _ => Err(SanError::Ambiguous(san.to_owned())),
+        }
+    }
+
+    /// Long algebraic notation as used by UCI, "e2e4" or "e7e8q"
+    pub fn to_uci(self) -> String {
+        let mut out = position_to_algebraic(self.from) + &position_to_algebraic(self.to);
+        if let Some(promotion) = self.promotion {
+            out.push(get_entity_fen(&promotion));
+        }
+        out
+    }
+
+    /// Reads a UCI move, the flags are left empty until it meets a board through `create_move`
+    pub fn from_uci(text: &str) -> Option<Move> {
+        let text = text.trim();
+        if !text.is_ascii() || (text.len() != 4 && text.len() != 5) {
+            return None;
+        }
+
+        let from = algebraic_to_position(&text[0..2])?;
+        let to = algebraic_to_position(&text[2..4])?;
+        let promotion = match text[4..].chars().next() {
+            Some(c) => Some(uci_promotion(c)?),
+            None => None,
+        };
+        Some(Move { from, to, promotion, flags: MoveFlags::default() })
+    }
+}
+
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_uci())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const PROMOTIONS: &str = "r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1";
+
+    fn san(fen: &str, uci: &str) -> String {
+        let board = Board::from_fen(fen).unwrap();
+        Move::from_uci(uci).unwrap().to_san(&board)
+    }
+
+    fn parse(fen: &str, san: &str) -> Result<String, SanError> {
+        let board = Board::from_fen(fen).unwrap();
+        Move::from_san(&board, san).map(|mv| mv.to_uci())
+    }
+
+    #[test]
+    fn san_round_trips() {
+        for fen in [DEFAULTFEN, KIWIPETE, PROMOTIONS] {
+            let board = Board::from_fen(fen).unwrap();
+            for mv in legal_moves(&board) {
+                assert_eq!(Move::from_san(&board, &mv.to_san(&board)), Ok(mv), "{} in {}", mv, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn formats_san() {
+        assert_eq!(san(DEFAULTFEN, "g1f3"), "Nf3");
+        assert_eq!(san(DEFAULTFEN, "e2e4"), "e4");
+        assert_eq!(san(KIWIPETE, "e1g1"), "O-O");
+        assert_eq!(san(KIWIPETE, "e1c1"), "O-O-O");
+        assert_eq!(san(KIWIPETE, "e5f7"), "Nxf7");
+        assert_eq!(san(KIWIPETE, "d5e6"), "dxe6");
+        assert_eq!(san(PROMOTIONS, "b7a8q"), "bxa8=Q+");
+        assert_eq!(san(PROMOTIONS, "b7b8n"), "b8=N");
+        // a file letter is enough for the knights, the rooks share one so they need the rank
+        assert_eq!(san("8/8/8/8/8/8/8/N1N1K2k w - - 0 1", "a1b3"), "Nab3");
+        assert_eq!(san("7k/8/8/8/8/8/R7/R3K3 w - - 0 1", "a1a3"), "R1a3");
+        assert_eq!(san("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "d1d8"), "Rd8#");
+        assert_eq!(san("4k3/8/8/8/8/8/8/3RK3 w - - 0 1", "d1d8"), "Rd8+");
+    }
+
+    #[test]
+    fn parses_san() {
+        assert_eq!(parse(DEFAULTFEN, "Nf3"), Ok("g1f3".to_owned()));
+        assert_eq!(parse(DEFAULTFEN, "e4!?"), Ok("e2e4".to_owned()));
+        assert_eq!(parse(KIWIPETE, "0-0-0"), Ok("e1c1".to_owned()));
+        assert_eq!(parse(KIWIPETE, "Nxf7"), Ok("e5f7".to_owned()));
+        assert_eq!(parse(PROMOTIONS, "bxa8Q"), Ok("b7a8q".to_owned()));
+        assert_eq!(parse(PROMOTIONS, "b8=R"), Ok("b7b8r".to_owned()));
+        assert_eq!(parse("8/8/8/8/8/8/8/N1N1K2k w - - 0 1", "Nab3"), Ok("a1b3".to_owned()));
+        assert_eq!(parse("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", "Rd8#"), Ok("d1d8".to_owned()));
+    }
+
+    #[test]
+    fn rejects_bad_san() {
+        assert_eq!(parse(DEFAULTFEN, " "), Err(SanError::Empty));
+        assert_eq!(parse(DEFAULTFEN, "Zz9"), Err(SanError::InvalidSyntax("Zz9".to_owned())));
+        assert_eq!(parse(DEFAULTFEN, "e5"), Err(SanError::NoMatchingMove("e5".to_owned())));
+        assert_eq!(parse(DEFAULTFEN, "O-O"), Err(SanError::NoMatchingMove("O-O".to_owned())));
+        assert_eq!(parse(PROMOTIONS, "b8"), Err(SanError::NoMatchingMove("b8".to_owned())));
+        assert_eq!(parse("8/8/8/8/8/8/8/N1N1K2k w - - 0 1", "Nb3"), Err(SanError::Ambiguous("Nb3".to_owned())));
+    }
+}
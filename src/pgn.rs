@@ -0,0 +1,560 @@
+use crate::chess_engine::*;
+use crate::components::MatchData;
+use crate::notation::SanError;
+
+/// Why a PGN text couldn't be read back into a game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    NoGame,
+    InvalidTag(String),
+    UnterminatedComment,
+    UnbalancedVariation,
+    InvalidFen(FenError),
+    InvalidPosition(PositionError),
+    IllegalMove { ply: usize, error: SanError },
+    UnplayableMove { ply: usize, san: String, error: MoveError }, // understood, but the board wouldn't take it
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::NoGame => write!(f, "no game found"),
+            PgnError::InvalidTag(tag) => write!(f, "invalid tag pair '{}'", tag),
+            PgnError::UnterminatedComment => write!(f, "comment is missing its closing '}}'"),
+            PgnError::UnbalancedVariation => write!(f, "variation parentheses don't match up"),
+            PgnError::InvalidFen(err) => write!(f, "FEN tag is invalid: {}", err),
+            PgnError::InvalidPosition(err) => write!(f, "FEN tag is not a legal position: {}", err),
+            PgnError::IllegalMove { ply, error } => write!(f, "ply {}: {}", ply, error),
+            PgnError::UnplayableMove { ply, san, error } => write!(f, "ply {}: '{}' can't be played: {}", ply, san, error),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// A move in the movetext along with everything annotated onto it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnMove {
+    pub mv: Move,
+    pub san: String,
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    pub variations: Vec<PgnLine>, // alternatives to this move, played from the position before it
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct PgnLine {
+    pub comment: Option<String>, // comment before the first move
+    pub moves: Vec<PgnMove>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub line: PgnLine,
+    pub result: String,
+}
+
+pub const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+impl Default for PgnGame {
+    fn default() -> Self {
+        Self {
+            tags: SEVEN_TAG_ROSTER.iter().map(|name| (name.to_string(), "?".to_owned())).collect(),
+            line: PgnLine::default(),
+            result: "*".to_owned(),
+        }
+    }
+}
+
+// days since 1970-01-01 to (year, month, day), see http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// "YYYY.MM.DD" for a unix timestamp, PGN's unknown date if there is none
+pub fn pgn_date(timestamp: f32) -> String {
+    if timestamp <= 0. {
+        return "????.??.??".to_owned();
+    }
+    let (year, month, day) = civil_from_days(timestamp as i64 / 86400);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// The PGN result of a finished board, "*" while the game is still going (or a draw could only be claimed)
+pub fn result_for(board: &Board) -> &'static str {
+    match board.outcome().forced_end() {
+        Some(end) => end.result.to_pgn(),
+        None => "*",
+    }
+}
+
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl PgnGame {
+    /// Builds the main line from moves played out from `start`, SAN is worked out along the way
+    pub fn from_moves(start: &Board, moves: &[Move]) -> Result<Self, PgnError> {
+        let mut game = Self::default();
+        let mut board = start.clone();
+        for (i, mv) in moves.iter().enumerate() {
+            let san = play_san(&mut board, *mv, i + 1)?;
+            game.line.moves.push(PgnMove { mv: *mv, san, nags: Vec::new(), comment: None, variations: Vec::new() });
+        }
+
+        if start.to_fen() != DEFAULTFEN {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &start.to_fen());
+        }
+        Ok(game)
+    }
+
+    /// Fills the Seven Tag Roster from the server's match info, player_1 plays white
+    pub fn set_match_data(&mut self, match_data: &MatchData) {
+        self.set_tag("Event", "Bitmato Chess Match");
+        self.set_tag("Site", "Bitmato Chess Server");
+        self.set_tag("Date", &pgn_date(match_data.time_started));
+        self.set_tag("Round", "-");
+        self.set_tag("White", &match_data.player_1);
+        self.set_tag("Black", &match_data.player_2);
+        self.set_tag("MatchId", &match_data.match_id);
+        if !match_data.time_control.is_empty() {
+            self.set_tag("TimeControl", &match_data.time_control);
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        if name == "Result" {
+            self.result = value.to_owned();
+        }
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some(pair) => pair.1 = value.to_owned(),
+            None => self.tags.push((name.to_owned(), value.to_owned())),
+        }
+    }
+
+    /// Fills in the Result and Termination tags for a finished game
+    pub fn set_game_end(&mut self, end: &GameEnd) {
+        self.set_tag("Result", end.result.to_pgn());
+        self.set_tag("Termination", end.termination.pgn_termination());
+    }
+
+    /// The result from the Result tag, None for an unfinished game
+    pub fn game_result(&self) -> Option<GameResult> {
+        GameResult::from_pgn(&self.result)
+    }
+
+    /// The position the movetext starts from, the FEN tag if there is one
+    pub fn start_board(&self) -> Result<Board, PgnError> {
+        let fen = self.tag("FEN").unwrap_or(DEFAULTFEN);
+        let board = Board::from_fen(fen).map_err(PgnError::InvalidFen)?;
+        board.validate_position().map_err(PgnError::InvalidPosition)?;
+        Ok(board)
+    }
+
+    pub fn main_line(&self) -> Vec<Move> {
+        self.line.moves.iter().map(|pgn_move| pgn_move.mv).collect()
+    }
+
+    /// Replays the main line and returns the final position
+    pub fn final_board(&self) -> Result<Board, PgnError> {
+        let mut board = self.start_board()?;
+        for (i, mv) in self.main_line().into_iter().enumerate() {
+            play_san(&mut board, mv, i + 1)?;
+        }
+        Ok(board)
+    }
+
+    /// The whole game as PGN text, fails if a move in it can't be played
+    pub fn to_pgn(&self) -> Result<String, PgnError> {
+        let mut out = String::new();
+        for (name, value) in &self.tags {
+            let value = if name == "Result" { &self.result } else { value };
+            out += &format!("[{} \"{}\"]\n", name, escape_tag_value(value));
+        }
+        out.push('\n');
+
+        let mut tokens: Vec<String> = Vec::new();
+        write_line(&self.line, &self.start_board()?, 0, &mut tokens)?;
+        tokens.push(self.result.clone());
+
+        // glue the parentheses onto what they wrap
+        let mut i = 0;
+        while i < tokens.len() {
+            if tokens[i] == "(" && i + 1 < tokens.len() {
+                let next = tokens.remove(i + 1);
+                tokens[i] += &next;
+                continue;
+            } else if tokens[i] == ")" && i > 0 {
+                tokens.remove(i);
+                tokens[i - 1].push(')');
+                continue;
+            }
+            i += 1;
+        }
+
+        // PGN export format keeps movetext lines under 80 characters
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() > 79 {
+                out.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                out.push(' ');
+                line_len += 1;
+            }
+            line_len += token.len();
+            out += &token;
+        }
+        out.push('\n');
+        Ok(out)
+    }
+}
+
+// plays the `ply`th move and hands back its SAN
+fn play_san(board: &mut Board, mv: Move, ply: usize) -> Result<String, PgnError> {
+    let san = mv.to_san(board);
+    match move_entity(board, mv, false) {
+        Ok(_) => Ok(san),
+        Err(error) => Err(PgnError::UnplayableMove { ply, san, error }),
+    }
+}
+
+fn write_line(line: &PgnLine, start: &Board, first_ply: usize, tokens: &mut Vec<String>) -> Result<(), PgnError> {
+    if let Some(comment) = &line.comment {
+        tokens.push(format!("{{{}}}", comment));
+    }
+
+    let mut board = start.clone();
+    let mut needs_number = true; // black's moves get a "N..." after anything that interrupts the flow
+    for (i, pgn_move) in line.moves.iter().enumerate() {
+        if board.current_turn == TeamLoyalty::WHITE {
+            tokens.push(format!("{}.", board.fullmove_number));
+        } else if needs_number {
+            tokens.push(format!("{}...", board.fullmove_number));
+        }
+        tokens.push(pgn_move.san.clone());
+        needs_number = false;
+
+        for nag in &pgn_move.nags {
+            tokens.push(format!("${}", nag));
+        }
+        if let Some(comment) = &pgn_move.comment {
+            tokens.push(format!("{{{}}}", comment));
+            needs_number = true;
+        }
+        for variation in &pgn_move.variations {
+            tokens.push("(".to_owned());
+            write_line(variation, &board, first_ply + i, tokens)?;
+            tokens.push(")".to_owned());
+            needs_number = true;
+        }
+
+        play_san(&mut board, pgn_move.mv, first_ply + i + 1)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    OpenVariation,
+    CloseVariation,
+    Result(String),
+    San(String),
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '[' => {
+                let end = chars[i..].iter().position(|c| *c == ']').map(|n| n + i);
+                let raw: String = match end {
+                    Some(end) => chars[i + 1..end].iter().collect(),
+                    None => return Err(PgnError::InvalidTag(chars[i..].iter().collect())),
+                };
+                let (name, value) = match raw.trim().split_once(char::is_whitespace) {
+                    Some((name, value)) => (name.to_owned(), value.trim()),
+                    None => return Err(PgnError::InvalidTag(raw)),
+                };
+                if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+                    return Err(PgnError::InvalidTag(raw));
+                }
+                let value = value[1..value.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\");
+                tokens.push(Token::Tag(name, value));
+                i = end.unwrap() + 1;
+            },
+            '{' => {
+                let end = match chars[i..].iter().position(|c| *c == '}') {
+                    Some(n) => n + i,
+                    None => return Err(PgnError::UnterminatedComment),
+                };
+                let comment: String = chars[i + 1..end].iter().collect();
+                tokens.push(Token::Comment(comment.trim().to_owned()));
+                i = end + 1;
+            },
+            ';' => {
+                let end = chars[i..].iter().position(|c| *c == '\n').map_or(chars.len(), |n| n + i);
+                let comment: String = chars[i + 1..end].iter().collect();
+                tokens.push(Token::Comment(comment.trim().to_owned()));
+                i = end;
+            },
+            '%' if i == 0 || chars[i - 1] == '\n' => {
+                // escaped line, skip it entirely
+                i = chars[i..].iter().position(|c| *c == '\n').map_or(chars.len(), |n| n + i);
+            },
+            '(' => { tokens.push(Token::OpenVariation); i += 1; },
+            ')' => { tokens.push(Token::CloseVariation); i += 1; },
+            _ if c.is_whitespace() => i += 1,
+            _ => {
+                let end = chars[i..].iter()
+                    .position(|c| c.is_whitespace() || "{}();[".contains(*c))
+                    .map_or(chars.len(), |n| n + i);
+                let word: String = chars[i..end].iter().collect();
+                i = end;
+
+                if let Some(nag) = word.strip_prefix('$') {
+                    if let Ok(nag) = nag.parse() {
+                        tokens.push(Token::Nag(nag));
+                    }
+                    continue;
+                }
+                if matches!(word.as_str(), "1-0" | "0-1" | "1/2-1/2" | "*") {
+                    tokens.push(Token::Result(word));
+                    continue;
+                }
+
+                // "12." "12..." or glued on like "12.e4"
+                let san = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                let san = if san.len() < word.len() && word.contains('.') { san } else { word.as_str() };
+                if san.is_empty() {
+                    continue;
+                }
+
+                // move suffix annotations are shorthand for the first six NAGs
+                let bare = san.trim_end_matches(['!', '?']);
+                tokens.push(Token::San(bare.to_owned()));
+                let nag = match &san[bare.len()..] {
+                    "!" => Some(1),
+                    "?" => Some(2),
+                    "!!" => Some(3),
+                    "??" => Some(4),
+                    "!?" => Some(5),
+                    "?!" => Some(6),
+                    _ => None,
+                };
+                if let Some(nag) = nag {
+                    tokens.push(Token::Nag(nag));
+                }
+            },
+        }
+    }
+    Ok(tokens)
+}
+
+// reads moves until the line's closing parenthesis (or the end of the game)
+fn parse_line(tokens: &[Token], i: &mut usize, start: &Board, ply: &mut usize, is_variation: bool) -> Result<PgnLine, PgnError> {
+    let mut line = PgnLine::default();
+    let mut board = start.clone();
+    let mut before_last = start.clone();
+
+    while *i < tokens.len() {
+        match &tokens[*i] {
+            Token::San(san) => {
+                *ply += 1;
+                let mv = Move::from_san(&board, san).map_err(|error| PgnError::IllegalMove { ply: *ply, error })?;
+                before_last = board.clone();
+                let san = play_san(&mut board, mv, *ply)?;
+                line.moves.push(PgnMove { mv, san, nags: Vec::new(), comment: None, variations: Vec::new() });
+            },
+            Token::Comment(text) => {
+                let comment = match line.moves.last_mut() {
+                    Some(last) => &mut last.comment,
+                    None => &mut line.comment,
+                };
+                *comment = Some(match comment.take() {
+                    Some(existing) => existing + " " + text,
+                    None => text.clone(),
+                });
+            },
+            Token::Nag(nag) => {
+                if let Some(last) = line.moves.last_mut() {
+                    last.nags.push(*nag);
+                }
+            },
+            Token::OpenVariation => {
+                // a variation replaces the move before it, so there has to be one
+                let last = line.moves.last_mut().ok_or(PgnError::UnbalancedVariation)?;
+                *i += 1;
+                let mut variation_ply = *ply - 1;
+                let variation = parse_line(tokens, i, &before_last, &mut variation_ply, true)?;
+                last.variations.push(variation);
+            },
+            Token::CloseVariation => {
+                return if is_variation { Ok(line) } else { Err(PgnError::UnbalancedVariation) };
+            },
+            Token::Result(_) | Token::Tag(_, _) => break,
+        }
+        *i += 1;
+    }
+
+    if is_variation {
+        return Err(PgnError::UnbalancedVariation);
+    }
+    Ok(line)
+}
+
+/// Reads every game in a PGN file
+pub fn import_pgn_games(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let tokens = tokenize(text)?;
+    let mut games: Vec<PgnGame> = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let mut game = PgnGame { tags: Vec::new(), line: PgnLine::default(), result: "*".to_owned() };
+        while let Some(Token::Tag(name, value)) = tokens.get(i) {
+            game.set_tag(name, value);
+            i += 1;
+        }
+
+        let start = game.start_board()?;
+        let mut ply = 0;
+        game.line = parse_line(&tokens, &mut i, &start, &mut ply, false)?;
+
+        if let Some(Token::Result(result)) = tokens.get(i) {
+            game.set_tag("Result", result);
+            i += 1;
+        }
+        games.push(game);
+    }
+
+    Ok(games)
+}
+
+/// Reads the first game in a PGN file
+pub fn import_pgn(text: &str) -> Result<PgnGame, PgnError> {
+    import_pgn_games(text)?.into_iter().next().ok_or(PgnError::NoGame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san_of(line: &PgnLine) -> Vec<&str> {
+        line.moves.iter().map(|pgn_move| pgn_move.san.as_str()).collect()
+    }
+
+    #[test]
+    fn tokenizes_comments() {
+        let tokens = tokenize("{ before } 1. e4 ; rest of the line\n% escaped line\ne5 {after}").unwrap();
+        assert_eq!(tokens, vec![
+            Token::Comment("before".to_owned()),
+            Token::San("e4".to_owned()),
+            Token::Comment("rest of the line".to_owned()),
+            Token::San("e5".to_owned()),
+            Token::Comment("after".to_owned()),
+        ]);
+        assert_eq!(tokenize("1. e4 { never closed"), Err(PgnError::UnterminatedComment));
+    }
+
+    #[test]
+    fn tokenizes_nags_and_move_numbers() {
+        let tokens = tokenize("12.e4!? $14 12... e5?? 1/2-1/2").unwrap();
+        assert_eq!(tokens, vec![
+            Token::San("e4".to_owned()),
+            Token::Nag(5),
+            Token::Nag(14),
+            Token::San("e5".to_owned()),
+            Token::Nag(4),
+            Token::Result("1/2-1/2".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn reads_annotations() {
+        let game = import_pgn("[Event \"Test \\\"quoted\\\"\"]\n\n{start} 1. e4 $1 {good} e5 2. Nf3 *").unwrap();
+        assert_eq!(game.tag("Event"), Some("Test \"quoted\""));
+        assert_eq!(game.line.comment.as_deref(), Some("start"));
+        assert_eq!(game.line.moves[0].nags, vec![1]);
+        assert_eq!(game.line.moves[0].comment.as_deref(), Some("good"));
+        assert_eq!(san_of(&game.line), vec!["e4", "e5", "Nf3"]);
+        assert_eq!(game.game_result(), None);
+    }
+
+    #[test]
+    fn reads_variations() {
+        let game = import_pgn("1. e4 e5 (1... c5 2. Nf3 (2. c3) d6) (1... e6) 2. Nf3 1-0").unwrap();
+        assert_eq!(san_of(&game.line), vec!["e4", "e5", "Nf3"]);
+        let variations = &game.line.moves[1].variations;
+        assert_eq!(variations.len(), 2);
+        assert_eq!(san_of(&variations[0]), vec!["c5", "Nf3", "d6"]);
+        assert_eq!(san_of(&variations[0].moves[1].variations[0]), vec!["c3"]);
+        assert_eq!(san_of(&variations[1]), vec!["e6"]);
+        assert_eq!(game.game_result(), Some(GameResult::WhiteWins));
+
+        assert_eq!(import_pgn("1. e4 (1. d4 *"), Err(PgnError::UnbalancedVariation));
+        assert_eq!(import_pgn("1. e4 ) e5 *"), Err(PgnError::UnbalancedVariation));
+        assert_eq!(import_pgn("(1. e4) 1. d4 *"), Err(PgnError::UnbalancedVariation));
+    }
+
+    #[test]
+    fn reports_the_ply_of_an_illegal_move() {
+        let error = import_pgn("1. e4 e5 2. Ke3 *").unwrap_err();
+        assert_eq!(error, PgnError::IllegalMove { ply: 3, error: SanError::NoMatchingMove("Ke3".to_owned()) });
+    }
+
+    #[test]
+    fn reports_why_a_move_could_not_be_played() {
+        // bare kings are a dead draw, nothing can be played after it
+        let kings = "[FEN \"4k3/8/8/8/8/8/8/4K3 w - - 0 1\"]\n\n1. Kd1 *";
+        assert_eq!(import_pgn(kings).unwrap_err(), PgnError::UnplayableMove { ply: 1, san: "Kd1".to_owned(), error: MoveError::GameOver });
+    }
+
+    #[test]
+    fn rejects_impossible_start_positions() {
+        let text = "[FEN \"4k3/8/8/8/8/8/8/4K3 w K - 0 1\"]\n\n*";
+        assert_eq!(import_pgn(text), Err(PgnError::InvalidPosition(PositionError::InvalidCastlingRights)));
+    }
+
+    #[test]
+    fn plays_on_after_a_repetition() {
+        let game = import_pgn("1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8 5. e4 e5 *").unwrap();
+        assert_eq!(game.line.moves.len(), 10);
+        let board = game.final_board().unwrap();
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 6");
+    }
+
+    #[test]
+    fn exports_what_it_imports() {
+        let text = "1. e4 e5 (1... c5 {Sicilian} 2. Nf3) 2. Nf3 $1 Nc6 *";
+        let game = import_pgn(text).unwrap();
+        let exported = game.to_pgn().unwrap();
+        assert!(exported.ends_with("1. e4 e5 (1... c5 {Sicilian} 2. Nf3) 2. Nf3 $1 Nc6 *\n"), "{}", exported);
+        assert_eq!(import_pgn(&exported).unwrap().line, game.line);
+
+        let start = Board::from_fen(DEFAULTFEN).unwrap();
+        let moves = [Move::from_uci("e2e4").unwrap(), Move::from_uci("d2d4").unwrap()];
+        assert_eq!(PgnGame::from_moves(&start, &moves), Err(PgnError::UnplayableMove { ply: 2, san: "d4".to_owned(), error: MoveError::NotYourTurn }));
+    }
+}
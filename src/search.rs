@@ -0,0 +1,674 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::chess_engine::*;
+use crate::evaluation::evaluate;
+
+pub const MATE_SCORE: i32 = 30000;
+const INFINITY: i32 = 32000;
+pub const MAX_DEPTH: u32 = 64;
+const MAX_PLY: usize = 128;
+const MAX_HISTORY: i32 = 600_000; // history never outranks a killer
+pub const DEFAULT_HASH_MB: usize = 16;
+
+/// Shared flag that stops a running search, clone it and hand it to whoever needs to stop it
+#[derive(Debug, Clone, Default)]
+pub struct StopSignal(Arc<AtomicBool>);
+
+impl StopSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// How far the search may go, whichever limit is hit first stops it
+#[derive(Debug, Clone)]
+pub struct SearchLimits {
+    pub depth: u32,
+    pub time: Option<Duration>,
+    pub multi_pv: usize, // how many of the best lines to report, analysis only
+    pub hash_mb: usize, // size of the transposition table
+    pub stop: Option<StopSignal>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self { depth: 5, time: None, multi_pv: 1, hash_mb: DEFAULT_HASH_MB, stop: None }
+    }
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> Self {
+        Self { depth: depth.clamp(1, MAX_DEPTH), ..Self::default() }
+    }
+
+    /// As deep as it gets within `time`, at least one ply is always searched
+    pub fn time(time: Duration) -> Self {
+        Self { depth: MAX_DEPTH, time: Some(time), ..Self::default() }
+    }
+
+    /// No limit at all, only the stop signal ends it
+    pub fn infinite(stop: StopSignal) -> Self {
+        Self { depth: MAX_DEPTH, stop: Some(stop), ..Self::default() }
+    }
+
+    pub fn with_multi_pv(mut self, lines: usize) -> Self {
+        self.multi_pv = lines.max(1);
+        self
+    }
+
+    pub fn with_hash_mb(mut self, hash_mb: usize) -> Self {
+        self.hash_mb = hash_mb.max(1);
+        self
+    }
+
+    pub fn with_stop(mut self, stop: StopSignal) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+}
+
+/// What the search settled on
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: i32, // centipawns for the side to move, mates are near MATE_SCORE
+    pub depth: u32, // deepest iteration that finished
+    pub nodes: u64,
+    pub pv: Vec<Move>, // the line the search expects, best move first
+}
+
+/// One of the lines analysis found, best move first
+#[derive(Debug, Clone, PartialEq)]
+pub struct PvLine {
+    pub score: i32, // centipawns for the side to move
+    pub pv: Vec<Move>,
+}
+
+impl PvLine {
+    pub fn mate_in(&self) -> Option<i32> {
+        mate_in(self.score)
+    }
+}
+
+/// Where analysis got to after one more ply of iterative deepening
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisInfo {
+    pub depth: u32,
+    pub nodes: u64, // counted over every iteration so far
+    pub elapsed: Duration,
+    pub lines: Vec<PvLine>, // best first, up to `multi_pv` of them
+}
+
+impl AnalysisInfo {
+    pub fn best(&self) -> Option<&PvLine> {
+        self.lines.first()
+    }
+
+    pub fn nodes_per_second(&self) -> u64 {
+        let micros = self.elapsed.as_micros().max(1) as u64;
+        self.nodes * 1_000_000 / micros
+    }
+}
+
+/// Mate in how many moves (negative when getting mated) if `score` is a mate score
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score.abs() < MATE_SCORE - MAX_PLY as i32 {
+        return None;
+    }
+    let plies = MATE_SCORE - score.abs();
+    let moves = (plies + 1) / 2;
+    Some(if score > 0 { moves } else { -moves })
+}
+
+fn in_check(board: &Board) -> bool {
+    match board.bitboards.king_square(board.current_turn) {
+        Some(king) => board.bitboards.is_attacked(king, board.current_turn.opposite()),
+        None => false,
+    }
+}
+
+fn move_index(mv: &Move) -> (usize, usize) {
+    ((mv.from.y * 8 + mv.from.x) as usize, (mv.to.y * 8 + mv.to.x) as usize)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower, // the score is at least this (beta cutoff)
+    Upper, // the score is at most this (nothing beat alpha)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TtEntry {
+    key: u64,
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    best_move: Option<Move>,
+}
+
+/// Positions already searched, keyed by the board's zobrist hash
+struct TranspositionTable {
+    entries: Vec<Option<TtEntry>>,
+}
+
+impl TranspositionTable {
+    fn new(size_mb: usize) -> Self {
+        let wanted = size_mb * 1024 * 1024 / std::mem::size_of::<Option<TtEntry>>();
+        // a power of two so the hash can just be masked into an index
+        let len = if wanted.is_power_of_two() { wanted } else { wanted.next_power_of_two() / 2 };
+        Self { entries: vec![None; len.max(1)] }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+
+    fn probe(&self, key: u64) -> Option<TtEntry> {
+        self.entries[self.index(key)].filter(|entry| entry.key == key)
+    }
+
+    // deeper results about the same position are worth more than shallow ones
+    fn store(&mut self, entry: TtEntry) {
+        let index = self.index(entry.key);
+        match self.entries[index] {
+            Some(old) if old.key == entry.key && old.depth > entry.depth => {},
+            _ => self.entries[index] = Some(entry),
+        }
+    }
+}
+
+// mate scores are stored as distance from the stored position, not from the root
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_SCORE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score <= -(MATE_SCORE - MAX_PLY as i32) {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_SCORE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score <= -(MATE_SCORE - MAX_PLY as i32) {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+struct Searcher {
+    board: Board,
+    nodes: u64,
+    deadline: Option<Instant>,
+    stop: Option<StopSignal>,
+    stopped: bool,
+    tt: TranspositionTable,
+    killers: [[Option<Move>; 2]; MAX_PLY], // quiet moves that caused a cutoff at each ply
+    history: Vec<[i32; 64]>, // [from][to], bumped every time a quiet move causes a cutoff
+}
+
+impl Searcher {
+    fn new(board: &Board, limits: &SearchLimits) -> Self {
+        Self {
+            board: board.clone(),
+            nodes: 0,
+            deadline: limits.time.map(|time| Instant::now() + time),
+            stop: limits.stop.clone(),
+            stopped: false,
+            tt: TranspositionTable::new(limits.hash_mb),
+            killers: [[None; 2]; MAX_PLY],
+            history: vec![[0; 64]; 64],
+        }
+    }
+
+    // only every so often, asking the clock is not free
+    fn check_time(&mut self) {
+        if self.nodes.is_multiple_of(2048) {
+            if let Some(deadline) = self.deadline {
+                self.stopped |= Instant::now() >= deadline;
+            }
+            if let Some(stop) = &self.stop {
+                self.stopped |= stop.is_stopped();
+            }
+        }
+    }
+
+    // the table's move first, then captures (most valuable victim, least valuable attacker), promotions, killers and history
+    fn move_score(&self, mv: &Move, ply: usize, tt_move: Option<Move>) -> i32 {
+        if tt_move == Some(*mv) {
+            return 2_000_000;
+        }
+        if mv.flags.capture {
+            let victim = if mv.flags.en_passant {
+                EntityType::PAWN
+            } else {
+                self.board.entity_at(mv.to).map(|ent| ent.entity_type).unwrap_or(EntityType::PAWN)
+            };
+            let attacker = self.board.entity_at(mv.from).map(|ent| ent.entity_type).unwrap_or(EntityType::PAWN);
+            return 1_000_000 + piece_value(victim) * 10 - piece_value(attacker);
+        }
+        if let Some(promotion) = mv.promotion {
+            return 900_000 + piece_value(promotion);
+        }
+        if self.killers[ply][0] == Some(*mv) {
+            return 800_000;
+        }
+        if self.killers[ply][1] == Some(*mv) {
+            return 700_000;
+        }
+        let (from, to) = move_index(mv);
+        self.history[from][to]
+    }
+
+    fn order_moves(&self, moves: &mut [Move], ply: usize, tt_move: Option<Move>) {
+        moves.sort_by_cached_key(|mv| -self.move_score(mv, ply, tt_move));
+    }
+
+    fn store_cutoff(&mut self, mv: Move, depth: u32, ply: usize) {
+        if mv.flags.capture || mv.promotion.is_some() {
+            return;
+        }
+        if self.killers[ply][0] != Some(mv) {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = Some(mv);
+        }
+        let (from, to) = move_index(&mv);
+        self.history[from][to] = (self.history[from][to] + (depth * depth) as i32).min(MAX_HISTORY);
+    }
+
+    // older iterations count for less, otherwise long analysis just piles up against the cap
+    fn age_history(&mut self) {
+        for row in self.history.iter_mut() {
+            for score in row.iter_mut() {
+                *score /= 2;
+            }
+        }
+    }
+
+    // the best line at the root leaving out `excluded`, None once there's nothing left or the search got stopped
+    fn search_root(&mut self, depth: u32, excluded: &[Move]) -> Option<PvLine> {
+        let tt_move = self.tt.probe(self.board.hash).and_then(|entry| entry.best_move);
+        let mut moves: Vec<Move> = legal_moves(&self.board).into_iter()
+            .filter(|mv| !excluded.contains(mv))
+            .collect();
+        self.order_moves(&mut moves, 0, tt_move);
+
+        let mut best: Option<PvLine> = None;
+        let mut alpha = -INFINITY;
+        let mut child_pv = Vec::new();
+        for mv in moves {
+            let undo = self.board.make_move_quiet(mv);
+            let score = -self.negamax(depth - 1, 1, -INFINITY, -alpha, &mut child_pv);
+            self.board.unmake_move(undo);
+            if self.stopped {
+                return None;
+            }
+
+            if score > alpha {
+                alpha = score;
+                let mut pv = vec![mv];
+                pv.extend_from_slice(&child_pv);
+                best = Some(PvLine { score, pv });
+            }
+        }
+
+        let mut best = best?;
+        // lines searched with moves left out aren't the real value of the position
+        if excluded.is_empty() {
+            self.tt.store(TtEntry { key: self.board.hash, depth, score: best.score, bound: Bound::Exact, best_move: best.pv.first().copied() });
+        }
+        self.extend_pv(&mut best.pv, depth);
+        Some(best)
+    }
+
+    // table hits cut the collected line short, so follow the table's moves for the rest of it
+    fn extend_pv(&mut self, pv: &mut Vec<Move>, depth: u32) {
+        let mut undos: Vec<UndoInfo> = pv.iter().map(|mv| self.board.make_move_quiet(*mv)).collect();
+        while pv.len() < depth as usize && self.board.repetition_count() < 2 {
+            let next = match self.tt.probe(self.board.hash).and_then(|entry| entry.best_move) {
+                Some(next) => next,
+                None => break,
+            };
+            if !legal_moves(&self.board).contains(&next) {
+                break;
+            }
+            undos.push(self.board.make_move_quiet(next));
+            pv.push(next);
+        }
+        while let Some(undo) = undos.pop() {
+            self.board.unmake_move(undo);
+        }
+    }
+
+    fn negamax(&mut self, depth: u32, ply: usize, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        pv.clear();
+        self.nodes += 1;
+        self.check_time();
+        if self.stopped {
+            return 0;
+        }
+
+        // coming back to a position is as good as a draw
+        if ply > 0 && (self.board.halfmove_clock >= 100 || self.board.repetition_count() >= 2) {
+            return 0;
+        }
+        if depth == 0 || ply >= MAX_PLY - 1 {
+            return self.quiescence(ply, alpha, beta);
+        }
+
+        let key = self.board.hash;
+        let mut tt_move = None;
+        if let Some(entry) = self.tt.probe(key) {
+            tt_move = entry.best_move;
+            if entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                let usable = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if usable {
+                    return score;
+                }
+            }
+        }
+
+        let mut moves = legal_moves(&self.board);
+        if moves.is_empty() {
+            return if in_check(&self.board) { -MATE_SCORE + ply as i32 } else { 0 };
+        }
+        self.order_moves(&mut moves, ply, tt_move);
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        let mut child_pv = Vec::new();
+        for mv in moves {
+            let undo = self.board.make_move_quiet(mv);
+            let score = -self.negamax(depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            self.board.unmake_move(undo);
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best {
+                best = score;
+                best_move = Some(mv);
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(mv);
+                    pv.extend_from_slice(&child_pv);
+                }
+            }
+            if alpha >= beta {
+                self.store_cutoff(mv, depth, ply);
+                break;
+            }
+        }
+
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(TtEntry { key, depth, score: score_to_tt(best, ply), bound, best_move });
+        best
+    }
+
+    // keeps going through captures so the search doesn't stop halfway through an exchange
+    fn quiescence(&mut self, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        self.check_time();
+        if self.stopped {
+            return 0;
+        }
+
+        let stand_pat = evaluate(&self.board);
+        if stand_pat >= beta || ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves: Vec<Move> = legal_moves(&self.board).into_iter()
+            .filter(|mv| mv.flags.capture || mv.promotion == Some(EntityType::QUEEN))
+            .collect();
+        self.order_moves(&mut moves, ply, None);
+
+        for mv in moves {
+            let undo = self.board.make_move_quiet(mv);
+            let score = -self.quiescence(ply + 1, -beta, -alpha);
+            self.board.unmake_move(undo);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+}
+
+struct Analysis {
+    searcher: Searcher,
+    started: Instant,
+    depth: u32,
+    max_depth: u32,
+    multi_pv: usize,
+    finished: bool,
+}
+
+impl Iterator for Analysis {
+    type Item = AnalysisInfo;
+
+    fn next(&mut self) -> Option<AnalysisInfo> {
+        if self.finished || self.depth >= self.max_depth {
+            return None;
+        }
+        let depth = self.depth + 1;
+
+        // the first ply always finishes (unless stopped) so there is a move to hand back
+        let deadline = self.searcher.deadline;
+        if depth == 1 {
+            self.searcher.deadline = None;
+        }
+        self.searcher.age_history();
+        let mut lines: Vec<PvLine> = Vec::new();
+        while lines.len() < self.multi_pv {
+            let excluded: Vec<Move> = lines.iter().map(|line| line.pv[0]).collect();
+            match self.searcher.search_root(depth, &excluded) {
+                Some(line) => lines.push(line),
+                None => break,
+            }
+        }
+        self.searcher.deadline = deadline;
+
+        if self.searcher.stopped || lines.is_empty() {
+            self.finished = true;
+            return None;
+        }
+        self.depth = depth;
+        // no point looking deeper once a forced mate has been found
+        self.finished = lines[0].mate_in().is_some();
+
+        Some(AnalysisInfo {
+            depth,
+            nodes: self.searcher.nodes,
+            elapsed: self.started.elapsed(),
+            lines,
+        })
+    }
+}
+
+/// Iterative deepening over `board`, yielding the best `limits.multi_pv` lines after every finished ply.
+/// Ends at the depth or time limit, on a forced mate, or when `limits.stop` gets signalled
+pub fn analyze(board: &Board, limits: SearchLimits) -> impl Iterator<Item = AnalysisInfo> {
+    Analysis {
+        searcher: Searcher::new(board, &limits),
+        started: Instant::now(),
+        depth: 0,
+        max_depth: limits.depth.clamp(1, MAX_DEPTH),
+        multi_pv: limits.multi_pv.max(1),
+        finished: false,
+    }
+}
+
+/// Looks for the best move for the side to move, deepening one ply at a time until a limit is hit
+pub fn search(board: &Board, limits: SearchLimits) -> SearchResult {
+    let mut result = SearchResult::default();
+    if let Some(info) = analyze(board, limits.with_multi_pv(1)).last() {
+        let line = &info.lines[0];
+        result = SearchResult {
+            best_move: line.pv.first().copied(),
+            score: line.score,
+            depth: info.depth,
+            nodes: info.nodes,
+            pv: line.pv.clone(),
+        };
+    }
+    result
+}
+
+/// Just the move from `search`, None if there is nothing to play
+pub fn best_move(board: &Board, limits: SearchLimits) -> Option<Move> {
+    search(board, limits).best_move
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    // alpha-beta in the order the moves are generated, no table, killers or history to get it wrong
+    fn reference(searcher: &mut Searcher, depth: u32, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        if ply > 0 && (searcher.board.halfmove_clock >= 100 || searcher.board.repetition_count() >= 2) {
+            return 0;
+        }
+        if depth == 0 {
+            return searcher.quiescence(ply, alpha, beta);
+        }
+        let moves = legal_moves(&searcher.board);
+        if moves.is_empty() {
+            return if in_check(&searcher.board) { -MATE_SCORE + ply as i32 } else { 0 };
+        }
+        let mut best = -INFINITY;
+        for mv in moves {
+            let undo = searcher.board.make_move_quiet(mv);
+            best = best.max(-reference(searcher, depth - 1, ply + 1, -beta, -alpha));
+            searcher.board.unmake_move(undo);
+            alpha = alpha.max(best);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let result = search(&board, SearchLimits::depth(4));
+        assert_eq!(result.best_move, Move::from_uci("d1d8").map(|mv| create_move(&board, mv.from, mv.to, None)));
+        assert_eq!(mate_in(result.score), Some(1));
+        assert!(result.depth < 4); // nothing to gain from looking further
+
+        // and the other side sees it coming
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 b - - 0 1").unwrap();
+        let result = search(&board, SearchLimits::depth(3));
+        assert_eq!(mate_in(result.score), None);
+    }
+
+    #[test]
+    fn ordering_does_not_change_the_score() {
+        for (fen, depth) in [(DEFAULTFEN, 4), (POSITION_3, 4), (POSITION_6, 2)] {
+            let board = Board::from_fen(fen).unwrap();
+            // iterative deepening leaves the table, killers and history filled in from the shallower plies
+            let result = search(&board, SearchLimits::depth(depth));
+            let mut plain = Searcher::new(&board, &SearchLimits::default());
+            assert_eq!(result.score, reference(&mut plain, depth, 0, -INFINITY, INFINITY), "{}", fen);
+            assert_eq!(result.depth, depth);
+        }
+    }
+
+    #[test]
+    fn history_stays_below_the_killers() {
+        let board = Board::from_fen(DEFAULTFEN).unwrap();
+        let mut searcher = Searcher::new(&board, &SearchLimits::default());
+        let mv = Move::from_uci("g1f3").unwrap();
+        for _ in 0..10_000 {
+            searcher.store_cutoff(mv, MAX_DEPTH, 1);
+        }
+        let (from, to) = move_index(&mv);
+        assert_eq!(searcher.history[from][to], MAX_HISTORY);
+        searcher.age_history();
+        assert_eq!(searcher.history[from][to], MAX_HISTORY / 2);
+    }
+
+    #[test]
+    fn no_move_when_the_game_is_over() {
+        let mated = Board::from_fen("3R2k1/5ppp/8/8/8/8/5PPP/6K1 b - - 1 1").unwrap();
+        let stalemated = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        for board in [mated, stalemated] {
+            assert_eq!(best_move(&board, SearchLimits::depth(3)), None);
+            assert_eq!(search(&board, SearchLimits::depth(3)), SearchResult::default());
+        }
+    }
+
+    #[test]
+    fn analysis_reports_multi_pv_lines() {
+        let board = Board::from_fen(DEFAULTFEN).unwrap();
+        let infos: Vec<AnalysisInfo> = analyze(&board, SearchLimits::depth(3).with_multi_pv(4)).collect();
+        assert_eq!(infos.iter().map(|info| info.depth).collect::<Vec<u32>>(), vec![1, 2, 3]);
+        for info in &infos {
+            assert_eq!(info.lines.len(), 4);
+            let mut firsts: Vec<String> = info.lines.iter().map(|line| line.pv[0].to_uci()).collect();
+            firsts.sort();
+            firsts.dedup();
+            assert_eq!(firsts.len(), 4, "{:?}", info.lines);
+            assert!(info.lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        }
+
+        // only as many lines as there are moves
+        let board = Board::from_fen("7k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let info = analyze(&board, SearchLimits::depth(1).with_multi_pv(10)).last().unwrap();
+        assert_eq!(info.lines.len(), 3);
+    }
+
+    #[test]
+    fn analysis_stops_when_told() {
+        let board = Board::from_fen(DEFAULTFEN).unwrap();
+        let stop = StopSignal::new();
+        let signal = stop.clone();
+        let started = Instant::now();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            signal.stop();
+        });
+
+        let last = analyze(&board, SearchLimits::infinite(stop.clone())).last();
+        stopper.join().unwrap();
+        assert!(stop.is_stopped());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        // the first ply is done well before the stop comes in
+        assert!(last.is_some_and(|info| info.depth < MAX_DEPTH && !info.lines.is_empty()));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::bitboard::Bitboard;
+use crate::chess_engine::{Board, GameEntity, Move, Position, TeamLoyalty, Vec2};
+
+/// One of the 64 squares, numbered like the bitboards (`y * 8 + x`, so a8 is 0 and h1 is 63)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Square(u8);
+
+/// Why a square name like "e4" couldn't be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSquareError(pub String);
+
+impl std::fmt::Display for ParseSquareError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' is not a square", self.0)
+    }
+}
+
+impl std::error::Error for ParseSquareError {}
+
+impl Square {
+    /// `file` 0-7 is a-h, `rank` 0-7 is the 1st to the 8th rank
+    pub fn new(file: u8, rank: u8) -> Option<Square> {
+        if file < 8 && rank < 8 { Some(Square((7 - rank) * 8 + file)) } else { None }
+    }
+
+    pub fn from_index(index: usize) -> Option<Square> {
+        if index < 64 { Some(Square(index as u8)) } else { None }
+    }
+
+    pub fn from_position(pos: Position) -> Option<Square> {
+        if (0..8).contains(&pos.x) && (0..8).contains(&pos.y) {
+            Some(Square((pos.y * 8 + pos.x) as u8))
+        } else {
+            None
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn position(&self) -> Position {
+        Vec2 { x: (self.0 % 8) as i32, y: (self.0 / 8) as i32 }
+    }
+
+    pub fn bit(&self) -> Bitboard {
+        1 << self.0
+    }
+
+    /// 0 for the a-file up to 7 for the h-file
+    pub fn file(&self) -> u8 {
+        self.0 % 8
+    }
+
+    /// 0 for the 1st rank up to 7 for the 8th
+    pub fn rank(&self) -> u8 {
+        7 - self.0 / 8
+    }
+
+    pub fn file_char(&self) -> char {
+        (b'a' + self.file()) as char
+    }
+
+    pub fn rank_char(&self) -> char {
+        (b'1' + self.rank()) as char
+    }
+
+    /// The same file on the mirrored rank, a1 <-> a8, e4 <-> e5
+    pub fn flip(&self) -> Square {
+        Square(self.0 ^ 56)
+    }
+
+    /// Where this square is for someone looking at the board from `team`'s side,
+    /// black sees it turned all the way round (a1 <-> h8)
+    pub fn relative_to(&self, team: TeamLoyalty) -> Square {
+        if team == TeamLoyalty::BLACK { Square(63 - self.0) } else { *self }
+    }
+
+    /// The square `files` to the right and `ranks` up from here (from white's side), None off the board
+    pub fn offset(&self, files: i32, ranks: i32) -> Option<Square> {
+        let (file, rank) = (self.file() as i32 + files, self.rank() as i32 + ranks);
+        if (0..8).contains(&file) && (0..8).contains(&rank) {
+            Square::new(file as u8, rank as u8)
+        } else {
+            None
+        }
+    }
+
+    /// Every square, a8 through h1 in the order the board is stored
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..64).map(Square)
+    }
+
+    /// The squares in a bitboard, same order as `all`
+    pub fn in_bitboard(bitboard: Bitboard) -> impl Iterator<Item = Square> {
+        crate::bitboard::Squares(bitboard).map(|index| Square(index as u8))
+    }
+
+    /// a-h along one rank (0 is the 1st rank)
+    pub fn on_rank(rank: u8) -> impl Iterator<Item = Square> {
+        (0..8).filter_map(move |file| Square::new(file, rank))
+    }
+
+    /// 1st to 8th rank along one file (0 is the a-file)
+    pub fn on_file(file: u8) -> impl Iterator<Item = Square> {
+        (0..8).filter_map(move |rank| Square::new(file, rank))
+    }
+}
+
+impl std::fmt::Display for Square {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.file_char(), self.rank_char())
+    }
+}
+
+impl std::str::FromStr for Square {
+    type Err = ParseSquareError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let bytes = name.as_bytes();
+        if bytes.len() != 2 {
+            return Err(ParseSquareError(name.to_owned()));
+        }
+        let (file, rank) = (bytes[0].wrapping_sub(b'a'), bytes[1].wrapping_sub(b'1'));
+        Square::new(file, rank).ok_or_else(|| ParseSquareError(name.to_owned()))
+    }
+}
+
+impl From<Square> for Position {
+    fn from(square: Square) -> Self {
+        square.position()
+    }
+}
+
+impl Move {
+    /// Where the piece starts
+    pub fn source_square(&self) -> Square {
+        Square::from_position(self.from).expect("move starts off the board")
+    }
+
+    /// Where the piece lands
+    pub fn target_square(&self) -> Square {
+        Square::from_position(self.to).expect("move ends off the board")
+    }
+}
+
+impl Board {
+    /// The piece standing on `square`, if any
+    pub fn piece_at(&self, square: Square) -> Option<GameEntity> {
+        *self.entity_at(square.position())
+    }
+
+    /// Every piece on the board along with where it stands
+    pub fn pieces(&self) -> impl Iterator<Item = (Square, GameEntity)> + '_ {
+        Square::all().filter_map(move |square| self.piece_at(square).map(|ent| (square, ent)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(name: &str) -> Square {
+        name.parse().unwrap()
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for square in Square::all() {
+            assert_eq!(square.to_string().parse(), Ok(square));
+        }
+        assert_eq!(square("a8").index(), 0);
+        assert_eq!(square("h1").index(), 63);
+        assert_eq!(square("e4"), Square::new(4, 3).unwrap());
+        assert_eq!(square("e4").position(), Vec2 { x: 4, y: 4 });
+        assert_eq!(Square::from_position(Vec2 { x: 4, y: 4 }), Some(square("e4")));
+        assert_eq!(Square::from_position(Vec2 { x: 8, y: 0 }), None);
+        for bad in ["", "e", "e9", "i1", "E4", "e44", "é4"] {
+            assert_eq!(bad.parse::<Square>(), Err(ParseSquareError(bad.to_owned())), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn flips_and_turns() {
+        assert_eq!(square("a1").flip(), square("a8"));
+        assert_eq!(square("e4").flip(), square("e5"));
+        assert_eq!(square("c2").relative_to(TeamLoyalty::WHITE), square("c2"));
+        assert_eq!(square("a1").relative_to(TeamLoyalty::BLACK), square("h8"));
+        assert_eq!(square("e4").relative_to(TeamLoyalty::BLACK), square("d5"));
+        for square in Square::all() {
+            assert_eq!(square.flip().flip(), square);
+            assert_eq!(square.relative_to(TeamLoyalty::BLACK).relative_to(TeamLoyalty::BLACK), square);
+        }
+    }
+
+    #[test]
+    fn offsets_stay_on_the_board() {
+        assert_eq!(square("e4").offset(1, 2), Some(square("f6")));
+        assert_eq!(square("e4").offset(-4, -3), Some(square("a1")));
+        assert_eq!(square("e4").offset(0, 0), Some(square("e4")));
+        assert_eq!(square("h8").offset(1, 0), None);
+        assert_eq!(square("a1").offset(0, -1), None);
+    }
+
+    #[test]
+    fn walks_ranks_and_files() {
+        let names = |squares: Vec<Square>| squares.iter().map(|square| square.to_string()).collect::<Vec<String>>().join(" ");
+        assert_eq!(names(Square::on_rank(0).collect()), "a1 b1 c1 d1 e1 f1 g1 h1");
+        assert_eq!(names(Square::on_file(4).collect()), "e1 e2 e3 e4 e5 e6 e7 e8");
+        assert_eq!(Square::on_rank(8).count(), 0);
+        assert!(Square::on_file(2).all(|square| square.file() == 2 && square.file_char() == 'c'));
+        assert_eq!(names(Square::in_bitboard(square("h1").bit() | square("a8").bit()).collect()), "a8 h1");
+    }
+
+    #[test]
+    fn moves_know_their_squares() {
+        let mv = Move::from_uci("g1f3").unwrap();
+        assert_eq!((mv.source_square(), mv.target_square()), (square("g1"), square("f3")));
+    }
+}